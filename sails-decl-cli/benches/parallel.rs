@@ -0,0 +1,119 @@
+//! Generates a large Sails project and times `sails-decl-rs run` on it with a
+//! single thread and with every core.
+//!
+//!     cargo bench -p sails-decl-cli
+//!
+//! The fixture has 600 models and 900 helpers by default, like a large
+//! monorepo. `SAILS_DECL_BENCH_MODELS`, `SAILS_DECL_BENCH_HELPERS` and
+//! `SAILS_DECL_BENCH_RUNS` change its size and how many times each
+//! configuration is timed.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create fixture directory");
+    std::fs::write(path, contents).expect("Failed to write fixture file");
+}
+
+fn model(index: usize, models: usize) -> String {
+    let mut attributes = String::new();
+    for field in 0..12 {
+        attributes.push_str(&format!(
+            "    /** Field {field} of model {index}. */\n    field{field}: {{ type: '{}', required: {}, maxLength: 200 }},\n",
+            ["string", "number", "boolean", "json"][field % 4],
+            field % 3 == 0,
+        ));
+    }
+    attributes.push_str(&format!(
+        "    tags: {{ type: 'json', '$SD-type-hint': 'Array<{{ label: string; weight: number }}>' }},\n    parent: {{ model: 'model{}' }},\n    children: {{ collection: 'model{}', via: 'parent' }},\n",
+        (index + 1) % models,
+        (index + models - 1) % models,
+    ));
+    format!(
+        "const shared = {{ archived: {{ type: 'boolean', defaultsTo: false }} }};\n\nmodule.exports = {{\n  attributes: {{\n    ...shared,\n{attributes}  }},\n  customToJSON: function() {{\n    return _.omit(this, ['field0']);\n  }},\n}};\n"
+    )
+}
+
+fn helper(index: usize) -> String {
+    let mut inputs = String::new();
+    for input in 0..6 {
+        inputs.push_str(&format!(
+            "    input{input}: {{ type: '{}', required: {}, description: 'Input {input}.' }},\n",
+            ["string", "number", "boolean", "ref"][input % 4],
+            input % 2 == 0,
+        ));
+    }
+    format!(
+        "module.exports = {{\n  friendlyName: 'Helper {index}',\n  description: 'Does thing number {index}.',\n  inputs: {{\n{inputs}  }},\n  exits: {{ success: {{}} }},\n  fn: async function (inputs) {{\n    return inputs.input0;\n  }}\n}};\n"
+    )
+}
+
+fn generate_fixture(root: &Path, models: usize, helpers: usize) {
+    write(&root.join(".sailsrc"), "{}\n");
+    write(
+        &root.join("config/models.js"),
+        "module.exports.models = {\n  attributes: {\n    createdAt: { type: 'number', autoCreatedAt: true },\n    updatedAt: { type: 'number', autoUpdatedAt: true },\n    id: { type: 'number', autoIncrement: true },\n  },\n};\n",
+    );
+    for index in 0..models {
+        write(&root.join(format!("api/models/Model{index}.js")), &model(index, models));
+    }
+    for index in 0..helpers {
+        write(
+            &root.join(format!("api/helpers/group-{}/do-thing-{index}.js", index % 30)),
+            &helper(index),
+        );
+    }
+}
+
+/// The fastest of `runs` runs, which is the least affected by other load.
+fn time_run(root: &Path, threads: Option<usize>, runs: usize) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let mut command = Command::new(env!("CARGO_BIN_EXE_sails-decl-rs"));
+            command.arg("run").arg(root);
+            if let Some(threads) = threads {
+                command.env("RAYON_NUM_THREADS", threads.to_string());
+            }
+            let start = Instant::now();
+            let output = command.output().expect("Failed to run sails-decl-rs");
+            let elapsed = start.elapsed();
+            assert!(
+                output.status.success(),
+                "sails-decl-rs failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            elapsed
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    // `cargo test --benches` passes harness flags; only time a real `cargo bench`
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+
+    let models = env_usize("SAILS_DECL_BENCH_MODELS", 600);
+    let helpers = env_usize("SAILS_DECL_BENCH_HELPERS", 900);
+    let runs = env_usize("SAILS_DECL_BENCH_RUNS", 5).max(1);
+
+    let root = std::env::temp_dir().join(format!("sails-decl-bench-{}", std::process::id()));
+    generate_fixture(&root, models, helpers);
+
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let sequential = time_run(&root, Some(1), runs);
+    let parallel = time_run(&root, None, runs);
+    std::fs::remove_dir_all(&root).ok();
+
+    println!("{} models, {} helpers, best of {} runs", models, helpers, runs);
+    println!("  1 thread:   {:>8.1} ms", sequential.as_secs_f64() * 1000.0);
+    println!("  {:<2} threads: {:>8.1} ms", cores, parallel.as_secs_f64() * 1000.0);
+    println!("  speedup:    {:>8.2}x", sequential.as_secs_f64() / parallel.as_secs_f64());
+}
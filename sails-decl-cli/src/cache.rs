@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use sails_decl_core::diagnostics::Diagnostic;
+use sails_decl_core::model::ModelSummary;
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Where the cache is kept, inside the types directory.
+pub const FILE_NAME: &str = ".sails-decl-cache";
+
+/// What earlier runs extracted from each model and helper, keyed by the hash
+/// of the files it came from, so unchanged files are not parsed again.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DiskCache {
+    /// Hash of the tool version and every setting that affects the output.
+    key: u64,
+    pub models: BTreeMap<PathBuf, CachedModel>,
+    pub helpers: Option<CachedHelpers>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedModel {
+    /// Hash of the model file and its dependencies.
+    pub source_hash: u64,
+    pub dependencies: Vec<PathBuf>,
+    pub summary: ModelSummary,
+    /// Hash of the model index the declaration was generated against, since
+    /// associations and type hints depend on the other models.
+    pub index_hash: u64,
+    pub output: CachedOutput,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedHelpers {
+    pub sources: Vec<PathBuf>,
+    /// Hash of every helper file and their dependencies.
+    pub source_hash: u64,
+    pub dependencies: Vec<PathBuf>,
+    pub index_hash: u64,
+    pub failed: Vec<PathBuf>,
+    pub output: CachedOutput,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedOutput {
+    pub code: String,
+    pub source_map: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl DiskCache {
+    /// Reads the cache in `types_dir`. A missing or unreadable cache is empty.
+    pub fn load(types_dir: &Path) -> Self {
+        std::fs::read_to_string(types_dir.join(FILE_NAME))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, types_dir: &Path) {
+        let json = serde_json::to_string(self).expect("Failed to serialize cache");
+        std::fs::create_dir_all(types_dir).expect("Failed to create types directory");
+        std::fs::write(types_dir.join(FILE_NAME), json).expect("Failed to write cache file");
+    }
+
+    /// Empties the cache if it was written with a different `key`.
+    pub fn validate(&mut self, key: u64) {
+        if self.key != key {
+            *self = DiskCache {
+                key,
+                ..DiskCache::default()
+            };
+        }
+    }
+}
+
+/// Hashes the contents of `files`, telling missing files apart from empty ones.
+pub fn hash_files<'a>(files: impl IntoIterator<Item = &'a Path>) -> u64 {
+    let mut hasher = Xxh3::new();
+    for file in files {
+        hasher.update(file.as_os_str().as_encoded_bytes());
+        match std::fs::read(file) {
+            Ok(contents) => {
+                hasher.write_u8(1);
+                hasher.write_u64(contents.len() as u64);
+                hasher.update(&contents);
+            }
+            Err(_) => hasher.write_u8(0),
+        }
+    }
+    hasher.digest()
+}
+
+pub fn hash_value(value: &impl std::hash::Hash) -> u64 {
+    let mut hasher = Xxh3::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
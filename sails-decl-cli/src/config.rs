@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use sails_decl_core::config::{Naming, Nullability};
+use serde::Deserialize;
+
+use crate::RunArgs;
+
+/// Read from the project root when `--config` is not given.
+pub const FILE_NAME: &str = "sails-decl.config.json";
+
+/// The `package.json` key the settings can be kept under instead.
+const PACKAGE_KEY: &str = "sailsDecl";
+
+/// Settings read from a config file. Relative paths are resolved against the
+/// directory of the file.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FileConfig {
+    model_dir: Option<PathBuf>,
+    helpers_dir: Option<PathBuf>,
+    types_dir: Option<PathBuf>,
+    #[serde(default)]
+    ignored_files: Vec<PathBuf>,
+    /// Globs relative to the project root.
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Globs relative to the model directory.
+    #[serde(default)]
+    model_include: Vec<String>,
+    /// Globs relative to the helpers directory.
+    #[serde(default)]
+    helpers_include: Vec<String>,
+    no_ignore: Option<bool>,
+    no_hooks: Option<bool>,
+    legacy: Option<bool>,
+    strict_type_hints: Option<bool>,
+    #[serde(default)]
+    type_imports: Vec<String>,
+    /// Sails attribute type to the TypeScript type written for it.
+    #[serde(default)]
+    type_overrides: BTreeMap<String, String>,
+    #[serde(default)]
+    naming: NamingConfig,
+    nullability: Option<NullabilityMode>,
+}
+
+/// Templates for the names of the types declared for each model, where
+/// `{name}` stands for the model name.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct NamingConfig {
+    model: Option<String>,
+    primary_key: Option<String>,
+    json: Option<String>,
+}
+
+/// Whether attribute types include `null`.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NullabilityMode {
+    /// Decided by each attribute's `allowNull`
+    Attribute,
+    /// Every attribute may be `null`
+    Always,
+    /// No attribute is ever `null`
+    Never,
+}
+
+impl From<NullabilityMode> for Nullability {
+    fn from(mode: NullabilityMode) -> Self {
+        match mode {
+            NullabilityMode::Attribute => Nullability::Attribute,
+            NullabilityMode::Always => Nullability::Always,
+            NullabilityMode::Never => Nullability::Never,
+        }
+    }
+}
+
+/// Fills in what `args` leaves unset from the config file: the one given with
+/// `--config`, else `sails-decl.config.json` or the `sailsDecl` key of
+/// `package.json` in `project_root`. Flags always win over the file.
+pub fn apply(args: &RunArgs, project_root: &Path) -> Result<RunArgs, String> {
+    let Some((path, file)) = find(args, project_root)? else {
+        return Ok(args.clone());
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let resolve = |relative: PathBuf| dir.join(relative);
+
+    let default = Naming::default();
+    let naming = Naming {
+        model: file.naming.model.unwrap_or(default.model),
+        primary_key: file.naming.primary_key.unwrap_or(default.primary_key),
+        json: file.naming.json.unwrap_or(default.json),
+    };
+    naming
+        .validate()
+        .map_err(|error| format!("Invalid naming template in {}: {}", path.display(), error))?;
+
+    Ok(RunArgs {
+        model_dir: args.model_dir.clone().or(file.model_dir.map(resolve)),
+        helpers_dir: args.helpers_dir.clone().or(file.helpers_dir.map(resolve)),
+        types_dir: args.types_dir.clone().or(file.types_dir.map(resolve)),
+        ignored_files: match args.ignored_files.is_empty() {
+            true => file.ignored_files.into_iter().map(resolve).collect(),
+            false => args.ignored_files.clone(),
+        },
+        exclude: match args.exclude.is_empty() {
+            true => file.exclude,
+            false => args.exclude.clone(),
+        },
+        model_include: match args.model_include.is_empty() {
+            true => file.model_include,
+            false => args.model_include.clone(),
+        },
+        helpers_include: match args.helpers_include.is_empty() {
+            true => file.helpers_include,
+            false => args.helpers_include.clone(),
+        },
+        no_ignore: switch(args.no_ignore, args.ignore).or(file.no_ignore).unwrap_or_default(),
+        no_hooks: switch(args.no_hooks, args.hooks).or(file.no_hooks).unwrap_or_default(),
+        legacy: switch(args.legacy, args.no_legacy).or(file.legacy).unwrap_or_default(),
+        strict_type_hints: switch(args.strict_type_hints, args.no_strict_type_hints)
+            .or(file.strict_type_hints)
+            .unwrap_or_default(),
+        type_imports: match args.type_imports.is_empty() {
+            true => file.type_imports,
+            false => args.type_imports.clone(),
+        },
+        nullability: args.nullability.or(file.nullability),
+        type_overrides: file.type_overrides,
+        naming,
+        ..args.clone()
+    })
+}
+
+/// What a flag and its opposite say, `None` if neither was given. Only the
+/// last of the two given is set.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+fn find(args: &RunArgs, project_root: &Path) -> Result<Option<(PathBuf, FileConfig)>, String> {
+    if let Some(path) = &args.config {
+        let file = read(path)?.ok_or_else(|| format!("{} has no \"{}\" key", path.display(), PACKAGE_KEY))?;
+        return Ok(Some((path.clone(), file)));
+    }
+    for path in [project_root.join(FILE_NAME), project_root.join("package.json")] {
+        if path.exists()
+            && let Some(file) = read(&path)?
+        {
+            return Ok(Some((path, file)));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the settings in `path`. For a `package.json`, they are under the
+/// `sailsDecl` key, and `None` if it has none.
+fn read(path: &Path) -> Result<Option<FileConfig>, String> {
+    let error = |e: &dyn std::fmt::Display| format!("Failed to read config file {}: {}", path.display(), e);
+    let json = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+    if path.file_name().is_some_and(|name| name == "package.json") {
+        let mut package: serde_json::Value = serde_json::from_str(&json).map_err(|e| error(&e))?;
+        return match package.get_mut(PACKAGE_KEY).map(serde_json::Value::take) {
+            Some(settings) => serde_json::from_value(settings).map(Some).map_err(|e| error(&e)),
+            None => Ok(None),
+        };
+    }
+    serde_json::from_str(&json).map(Some).map_err(|e| error(&e))
+}
@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+
+use crate::report::SkippedFile;
+
+/// Which of the files under the model and helpers directories are read.
+pub struct FileFilter<'a> {
+    pub project_root: &'a Path,
+    /// Path prefixes from `--ignored-files`.
+    pub ignored_files: &'a [PathBuf],
+    /// Matched against paths relative to the project root.
+    pub exclude: GlobSet,
+    /// Whether `.gitignore` and `.ignore` files are respected.
+    pub respect_ignore_files: bool,
+}
+
+/// Compiles `patterns`, leaving out the invalid ones with a warning. As in
+/// `.gitignore`, only `**` matches across directories.
+pub fn glob_set(patterns: &[String], option: &str, warnings: &mut Vec<String>) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match GlobBuilder::new(pattern).literal_separator(true).build() {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warnings.push(format!("Ignoring invalid {} pattern '{}': {}", option, pattern, e)),
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// Finds every model or helper source under `dir`, recording anything else
+/// that was found there as skipped. Files whose path relative to `dir`
+/// matches `include` are read as well, whatever their extension. Files
+/// ignored by `.gitignore` or `.ignore` are left out without being recorded.
+pub fn find_source_files(
+    dir: &Path,
+    include: &GlobSet,
+    filter: &FileFilter<'_>,
+    skipped: &mut Vec<SkippedFile>,
+) -> Vec<PathBuf> {
+    let mut walker = WalkBuilder::new(dir);
+    walker
+        .standard_filters(filter.respect_ignore_files)
+        .hidden(false)
+        // Projects that are not in a repository yet still mean their .gitignore
+        .require_git(false)
+        .follow_links(true)
+        .sort_by_file_name(|a, b| a.cmp(b));
+
+    let mut files = Vec::new();
+    for path in walker
+        .build()
+        .filter_map(Result::ok)
+        .map(ignore::DirEntry::into_path)
+        .filter(|path| path.is_file())
+    {
+        let relative_to_root = path.strip_prefix(filter.project_root).unwrap_or(&path);
+        let reason = if filter.ignored_files.iter().any(|ignored| path.starts_with(ignored)) {
+            "matched --ignored-files"
+        } else if filter.exclude.is_match(relative_to_root) {
+            "matched an exclude pattern"
+        } else if !sails_decl_core::source::is_source_file(&path)
+            && !include.is_match(path.strip_prefix(dir).unwrap_or(&path))
+        {
+            "not a JavaScript or TypeScript source file"
+        } else {
+            files.push(path);
+            continue;
+        };
+        skipped.push(SkippedFile { path, reason });
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A project with the given files, unique to `name`.
+    fn project(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("sails-decl-files-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        root
+    }
+
+    fn glob(patterns: &[&str]) -> GlobSet {
+        let patterns: Vec<String> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        glob_set(&patterns, "--exclude", &mut vec![])
+    }
+
+    /// The files found under `api/models`, relative to it.
+    fn find(root: &Path, filter: &FileFilter<'_>, include: &GlobSet) -> Vec<String> {
+        let dir = root.join("api/models");
+        find_source_files(&dir, include, filter, &mut vec![])
+            .iter()
+            .map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    fn filter<'a>(root: &'a Path, exclude: &[&str]) -> FileFilter<'a> {
+        FileFilter {
+            project_root: root,
+            ignored_files: &[],
+            exclude: glob(exclude),
+            respect_ignore_files: true,
+        }
+    }
+
+    #[test]
+    fn single_stars_stay_within_a_directory() {
+        let set = glob(&["api/models/*.test.js"]);
+        assert!(set.is_match("api/models/Pet.test.js"));
+        assert!(!set.is_match("api/models/nested/Pet.test.js"));
+
+        let set = glob(&["**/*.test.js"]);
+        assert!(set.is_match("api/models/Pet.test.js"));
+        assert!(set.is_match("api/models/nested/Pet.test.js"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        let mut warnings = vec![];
+        let set = glob_set(&["api/[models".to_string(), "**/*.js".to_string()], "--exclude", &mut warnings);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(set.is_match("api/models/Pet.js"));
+    }
+
+    #[test]
+    fn excluded_files_are_skipped() {
+        let root = project(
+            "exclude",
+            &["api/models/Pet.js", "api/models/Pet.test.js", "api/models/__fixtures__/Fake.js"],
+        );
+        let found = find(&root, &filter(&root, &["**/*.test.js", "**/__fixtures__/**"]), &GlobSet::empty());
+        assert_eq!(found, ["Pet.js"]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn includes_add_files_without_a_source_extension() {
+        let root = project("include", &["api/models/Pet.js", "api/models/Toy.model", "api/models/notes.txt"]);
+        let found = find(&root, &filter(&root, &[]), &glob(&["*.model"]));
+        assert_eq!(found, ["Pet.js", "Toy.model"]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn gitignored_files_are_left_out_unless_ignore_files_are_disabled() {
+        let root = project("gitignore", &["api/models/Pet.js", "api/models/Generated.js"]);
+        std::fs::write(root.join(".gitignore"), "Generated.js\n").unwrap();
+        assert_eq!(find(&root, &filter(&root, &[]), &GlobSet::empty()), ["Pet.js"]);
+
+        let filter = FileFilter {
+            respect_ignore_files: false,
+            ..filter(&root, &[])
+        };
+        assert_eq!(find(&root, &filter, &GlobSet::empty()), ["Generated.js", "Pet.js"]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::de::{Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+
+/// An installable hook the app depends on. Sails registers the models and
+/// helpers in its `api/` directory alongside the app's own.
+pub struct InstalledHook {
+    pub package: String,
+    pub model_dir: PathBuf,
+    pub helpers_dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    /// The names of the dependencies, in the order Sails loads them in.
+    #[serde(default, deserialize_with = "keys_in_order")]
+    dependencies: Vec<String>,
+    sails: Option<SailsField>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SailsField {
+    #[serde(default)]
+    is_hook: bool,
+}
+
+/// Finds the hooks Sails would load: the `dependencies` of the app's
+/// `package.json` that are installed in `node_modules` and marked with
+/// `"sails": { "isHook": true }`. A missing or unreadable `package.json`
+/// means there are none.
+pub fn find_hooks(project_root: &Path) -> Vec<InstalledHook> {
+    let Some(app) = read_package(&project_root.join("package.json")) else {
+        return vec![];
+    };
+    app.dependencies
+        .into_iter()
+        .filter_map(|package| {
+            let root = project_root.join("node_modules").join(&package);
+            let is_hook = read_package(&root.join("package.json"))?.sails?.is_hook;
+            is_hook.then(|| InstalledHook {
+                model_dir: root.join("api/models"),
+                helpers_dir: root.join("api/helpers"),
+                package,
+            })
+        })
+        .collect()
+}
+
+fn read_package(path: &Path) -> Option<Package> {
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+/// Reads the keys of a JSON object in the order they are written in.
+fn keys_in_order<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    struct Keys;
+
+    impl<'de> Visitor<'de> for Keys {
+        type Value = Vec<String>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("an object")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut keys = Vec::new();
+            while let Some((key, IgnoredAny)) = map.next_entry::<String, IgnoredAny>()? {
+                keys.push(key);
+            }
+            Ok(keys)
+        }
+    }
+
+    deserializer.deserialize_map(Keys)
+}
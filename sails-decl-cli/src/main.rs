@@ -1,6 +1,28 @@
-use std::path::PathBuf;
+mod cache;
+mod config;
+mod files;
+mod hooks;
+mod manifest;
+mod report;
+mod sailsrc;
+mod watch;
 
-use clap::{Parser, Subcommand};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rayon::prelude::*;
+use files::{FileFilter, find_source_files, glob_set};
+use globset::GlobSet;
+use hooks::InstalledHook;
+use report::{FileReport, OutputPaths, Report, SkippedFile, Status};
+use cache::{CachedHelpers, CachedModel, CachedOutput, DiskCache};
+use manifest::{Manifest, Orphan};
+use config::NullabilityMode;
+use sails_decl_core::config::{Naming, ProjectConfig, SailsPaths};
+use sails_decl_core::diagnostics::{Diagnosed, Diagnostic};
+use sails_decl_core::helpers::{HelperCache, generate_sails_helpers_cached};
+use sails_decl_core::model::{GenDeclarationsError, ModelIndex, ModelSummary, ParsedModel};
 use std::time::{Instant};
 
 #[derive(Parser)]
@@ -17,109 +39,546 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     #[command(name = "run")]
-    Run {
-        #[arg(value_parser)]
-        project_root: Option<PathBuf>,
-        #[arg(short = 'i', long = "ignored-files", value_parser)]
-        ignored_files: Vec<PathBuf>,
-        #[arg(short = 'm', long = "model-dir", value_parser)]
-        model_dir: Option<PathBuf>,
-        #[arg(short = 'e', long = "helpers-dir", value_parser)]
-        helpers_dir: Option<PathBuf>,
-        #[arg(short = 't', long = "types-dir", value_parser)]
-        types_dir: Option<PathBuf>
-    },
+    Run(RunArgs),
+    /// Regenerate the declarations whenever a model, helper or config file changes
+    #[command(name = "watch")]
+    Watch(RunArgs),
+}
+
+#[derive(Args, Default, Clone)]
+struct RunArgs {
+    #[arg(value_parser)]
+    project_root: Option<PathBuf>,
+    /// Read settings from this file instead of `sails-decl.config.json` or the
+    /// `sailsDecl` key of `package.json`
+    #[arg(short = 'c', long = "config", value_parser)]
+    config: Option<PathBuf>,
+    #[arg(short = 'i', long = "ignored-files", value_parser)]
+    ignored_files: Vec<PathBuf>,
+    /// Skip the files matching this glob, relative to the project root
+    #[arg(short = 'x', long = "exclude")]
+    exclude: Vec<String>,
+    /// Also read the files matching this glob as models, relative to the
+    /// model directory
+    #[arg(long = "model-include")]
+    model_include: Vec<String>,
+    /// Also read the files matching this glob as helpers, relative to the
+    /// helpers directory
+    #[arg(long = "helpers-include")]
+    helpers_include: Vec<String>,
+    /// Read files even if `.gitignore` or `.ignore` files exclude them
+    #[arg(long = "no-ignore", overrides_with = "ignore")]
+    no_ignore: bool,
+    /// Respect `.gitignore` and `.ignore` files even if the config file says not to
+    #[arg(long = "ignore", overrides_with = "no_ignore")]
+    ignore: bool,
+    /// Leave out the models and helpers of installed hooks
+    #[arg(long = "no-hooks", overrides_with = "hooks")]
+    no_hooks: bool,
+    /// Declare the models and helpers of installed hooks even if the config
+    /// file says not to
+    #[arg(long = "hooks", overrides_with = "no_hooks")]
+    hooks: bool,
+    #[arg(short = 'm', long = "model-dir", value_parser)]
+    model_dir: Option<PathBuf>,
+    #[arg(short = 'e', long = "helpers-dir", value_parser)]
+    helpers_dir: Option<PathBuf>,
+    #[arg(short = 't', long = "types-dir", value_parser)]
+    types_dir: Option<PathBuf>,
+    /// Interpret models using the Sails 0.12 attribute types
+    #[arg(long = "legacy", overrides_with = "no_legacy")]
+    legacy: bool,
+    /// Use the Sails 1.x attribute types even if the config file sets `legacy`
+    #[arg(long = "no-legacy", overrides_with = "legacy")]
+    no_legacy: bool,
+    /// Exit with an error if any `$SD-type-hint` fails to parse
+    #[arg(long = "strict-type-hints", overrides_with = "no_strict_type_hints")]
+    strict_type_hints: bool,
+    /// Only skip the attributes with an invalid `$SD-type-hint`, even if the
+    /// config file sets `strictTypeHints`
+    #[arg(long = "no-strict-type-hints", overrides_with = "strict_type_hints")]
+    no_strict_type_hints: bool,
+    /// Exit with an error if any model or helper could not be declared,
+    /// instead of only reporting it
+    #[arg(long = "fail-on-error")]
+    fail_on_error: bool,
+    /// Make a project type available to `$SD-type-hint` values, as `Name=module`
+    #[arg(long = "type-import", value_parser)]
+    type_imports: Vec<String>,
+    /// How to report what was generated
+    #[arg(long = "format", value_enum, default_value_t)]
+    format: Format,
+    /// Compare the generated files with the ones on disk instead of writing
+    /// them, and exit with an error if any are out of date
+    #[arg(long = "check")]
+    check: bool,
+    /// Parse every file instead of reusing what earlier runs cached in the
+    /// types directory, and leave the cache untouched
+    #[arg(long = "no-cache")]
+    no_cache: bool,
+    /// Whether attribute types include `null`
+    #[arg(long = "nullability", value_enum)]
+    nullability: Option<NullabilityMode>,
+    /// Sails attribute type to the TypeScript type written for it, only set
+    /// from the config file
+    #[arg(skip)]
+    type_overrides: BTreeMap<String, String>,
+    /// Only set from the config file
+    #[arg(skip)]
+    naming: Naming,
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+enum Format {
+    /// Progress messages on stdout, diagnostics on stderr
+    #[default]
+    Text,
+    /// A single JSON report on stdout
+    Json,
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Some(Commands::Run {
-            project_root,
-            ignored_files,
-            model_dir,
-            helpers_dir,
-            types_dir,
-        }) => run(
-            project_root,
-            ignored_files,
+        Some(Commands::Run(args)) => run(args),
+        Some(Commands::Watch(args)) => watch::watch(args),
+        None => run(&RunArgs::default()),
+    }
+}
+
+fn run(args: &RunArgs) {
+    let (args, paths) = load_project(args).unwrap_or_else(|error| exit_with_error(args, error));
+    let args = &args;
+    let text = args.format == Format::Text;
+    let mut cache = Cache::load(args, &paths);
+    let Generation {
+        mut report,
+        outputs,
+        type_hint_failures,
+    } = generate(args, &paths, &mut cache);
+
+    let mut stale = Vec::new();
+    if args.check {
+        for output in &outputs {
+            if let Some(diff) = output.diff_with_disk(&paths.root) {
+                if text {
+                    print!("{}", diff);
+                }
+                stale.push(output.path.clone());
+            }
+        }
+        let (_, orphans) = find_orphans(&paths, &outputs, &report);
+        for orphan in orphans {
+            if let Some(contents) = orphan.generated_contents() {
+                if text {
+                    print!("{}", unified_diff(&orphan.path, &paths.root, Some(&contents), None));
+                }
+                stale.push(orphan.path);
+            }
+        }
+        report.stale = Some(stale.clone());
+    } else {
+        for output in &outputs {
+            output.write();
+        }
+        remove_orphans(&paths, &outputs, &mut report, text);
+        cache.save(args, &paths);
+    }
+
+    let failed_files = report.failed_files();
+    report.success =
+        (failed_files == 0 || !args.fail_on_error) && type_hint_failures == 0 && stale.is_empty();
+    if !text {
+        print_json(&report);
+    }
+    if type_hint_failures > 0 && text {
+        eprintln!("Error: invalid $SD-type-hint found with --strict-type-hints");
+    } else if failed_files > 0 && text {
+        print_failed_files(args, failed_files);
+    }
+    if !stale.is_empty() && text {
+        eprintln!(
+            "Error: {} generated file(s) are out of date, run `sails-decl-rs run` to regenerate them",
+            stale.len()
+        );
+    }
+    if !report.success {
+        std::process::exit(1);
+    }
+}
+
+/// Reports the models and helpers that could not be declared, which only
+/// fails the run with `--fail-on-error`.
+fn print_failed_files(args: &RunArgs, failed_files: usize) {
+    let severity = if args.fail_on_error { "Error" } else { "Warning" };
+    eprintln!("{}: {} model(s) or helper(s) could not be declared", severity, failed_files);
+}
+
+/// The directories a run reads from and writes to.
+struct ProjectPaths {
+    root: PathBuf,
+    model_dir: PathBuf,
+    helpers_dir: PathBuf,
+    types_dir: PathBuf,
+    /// Hooks whose models and helpers are declared with the app's.
+    hooks: Vec<InstalledHook>,
+    /// Problems found while reading where Sails keeps models and helpers.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl ProjectPaths {
+    /// Fills in the directories `args` leaves unset from where Sails would
+    /// look, failing if `args` does not point at a Sails project. Every
+    /// directory is made absolute, as file watchers report absolute paths.
+    fn resolve(args: &RunArgs) -> Result<Self, String> {
+        let project_root = &absolute(project_root(args));
+
+        // Check for .sailsrc file in project_root
+        let sailsrc_path = project_root.join(".sailsrc");
+        if !sailsrc_path.exists() {
+            return Err(format!(".sailsrc file not found in project root: {}", project_root.display()));
+        }
+
+        // Sails lets `.sailsrc` override the config files, which override its defaults
+        let sailsrc = sailsrc::read_paths(&sailsrc_path)?;
+        let config = SailsPaths::load(project_root);
+        let sails_dir = |sailsrc: &Option<String>, config: &Option<String>, default: &str| {
+            project_root.join(sailsrc.as_deref().or(config.as_deref()).unwrap_or(default))
+        };
+
+        let model_dir = args.model_dir
+            .clone()
+            .map(absolute)
+            .unwrap_or_else(|| sails_dir(&sailsrc.models, &config.models, "api/models"));
+        let helpers_dir = args.helpers_dir
+            .clone()
+            .map(absolute)
+            .unwrap_or_else(|| sails_dir(&sailsrc.helpers, &config.helpers, "api/helpers"));
+        let types_dir = args.types_dir
+            .clone()
+            .map(absolute)
+            .unwrap_or_else(|| project_root.join("typings"));
+        Ok(ProjectPaths {
+            root: project_root.clone(),
             model_dir,
             helpers_dir,
             types_dir,
-        ),
-        None => run(&None, &vec![], &None, &None, &None),
+            hooks: match args.no_hooks {
+                true => vec![],
+                false => hooks::find_hooks(project_root),
+            },
+            diagnostics: config.diagnostics,
+        })
+    }
+
+    /// Where models are found, the app's before the hooks'.
+    fn model_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.model_dir)
+            .chain(self.hooks.iter().map(|hook| &hook.model_dir))
+            .cloned()
+            .collect()
+    }
+
+    /// Where helpers are found, the app's before the hooks'.
+    fn helper_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.helpers_dir)
+            .chain(self.hooks.iter().map(|hook| &hook.helpers_dir))
+            .cloned()
+            .collect()
+    }
+}
+
+fn absolute(path: PathBuf) -> PathBuf {
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+fn project_root(args: &RunArgs) -> PathBuf {
+    match &args.project_root {
+        Some(project_root) => project_root.clone(),
+        None => std::env::current_dir().expect("Failed to get current directory"),
     }
 }
 
-fn run(
-    project_root: &Option<PathBuf>,
-    ignored_files: &Vec<PathBuf>,
-    model_dir: &Option<PathBuf>,
-    helpers_dir: &Option<PathBuf>,
-    types_dir: &Option<PathBuf>,
-) {
-    let cwd = std::env::current_dir().expect("Failed to get current directory");
-    let project_root = project_root.as_ref().unwrap_or(&cwd);
+/// Merges the config file into `args` and finds the directories the result
+/// points at.
+fn load_project(args: &RunArgs) -> Result<(RunArgs, ProjectPaths), String> {
+    let args = config::apply(args, &project_root(args))?;
+    let paths = ProjectPaths::resolve(&args)?;
+    Ok((args, paths))
+}
 
-    // Check for .sailsrc file in project_root
-    let sailsrc_path = project_root.join(".sailsrc");
-    if !sailsrc_path.exists() {
-        eprintln!("Error: .sailsrc file not found in project root: {}", project_root.display());
-        std::process::exit(1);
+/// Reports why nothing could be generated.
+fn print_error(args: &RunArgs, error: String) {
+    match args.format {
+        Format::Text => eprintln!("Error: {}", error),
+        Format::Json => print_json(&Report {
+            project_root: project_root(args),
+            error: Some(error),
+            ..Report::default()
+        }),
     }
+}
+
+/// Reports why nothing could be generated, and exits.
+fn exit_with_error(args: &RunArgs, error: String) -> ! {
+    print_error(args, error);
+    std::process::exit(1);
+}
+
+/// Parsed sources kept between generations, so `watch` only parses what
+/// changed, and what earlier runs left on disk.
+#[derive(Default)]
+struct Cache {
+    models: HashMap<PathBuf, ParsedModel>,
+    helpers: HelperCache,
+    disk: DiskCache,
+}
 
-    let model_dir = model_dir
-        .as_ref()
-        .unwrap_or(&project_root.join("api/models"))
-        .clone();
-    let helpers_dir = helpers_dir
-        .as_ref()
-        .unwrap_or(&project_root.join("api/helpers"))
-        .clone();
-    let types_dir = types_dir
-        .as_ref()
-        .unwrap_or(&project_root.join("typings"))
-        .clone();
+impl Cache {
+    fn load(args: &RunArgs, paths: &ProjectPaths) -> Self {
+        Cache {
+            disk: match args.no_cache {
+                true => DiskCache::default(),
+                false => DiskCache::load(&paths.types_dir),
+            },
+            ..Cache::default()
+        }
+    }
+
+    fn save(&self, args: &RunArgs, paths: &ProjectPaths) {
+        if !args.no_cache {
+            self.disk.save(&paths.types_dir);
+        }
+    }
+}
+
+struct Generation {
+    report: Report,
+    outputs: Vec<OutputFile>,
+    type_hint_failures: usize,
+}
+
+/// Generates every declaration in memory.
+fn generate(args: &RunArgs, paths: &ProjectPaths, cache: &mut Cache) -> Generation {
+    let RunArgs {
+        ignored_files,
+        exclude,
+        model_include,
+        helpers_include,
+        no_ignore,
+        legacy,
+        strict_type_hints,
+        type_imports,
+        format,
+        nullability,
+        type_overrides,
+        naming,
+        ..
+    } = args;
+    let ProjectPaths {
+        root: project_root,
+        model_dir,
+        helpers_dir,
+        types_dir,
+        hooks,
+        diagnostics: path_diagnostics,
+    } = paths;
+    let text = *format == Format::Text;
+    let run_start = Instant::now();
+    let mut run_report = Report {
+        project_root: project_root.clone(),
+        ..Report::default()
+    };
     let models_types_dir = types_dir.join("models");
-    
+
     let models_start = Instant::now();
 
-    // recursively find all .js files in the model_dir, excluding ignored_files
-    let model_files = glob::glob(&format!("{}/**/*.js", model_dir.display()))
-        .expect("Failed to read glob pattern")
-        .filter_map(Result::ok)
-        .filter(|path| {
-            !ignored_files
-                .iter()
-                .any(|ignored| path.starts_with(ignored))
-        })
-        .collect::<Vec<_>>();
+    // recursively find all source files in the model_dir, excluding ignored_files
+    let filter = FileFilter {
+        project_root,
+        ignored_files,
+        exclude: glob_set(exclude, "--exclude", &mut run_report.warnings),
+        respect_ignore_files: !no_ignore,
+    };
+    let model_include = glob_set(model_include, "--model-include", &mut run_report.warnings);
+    let helpers_include = glob_set(helpers_include, "--helpers-include", &mut run_report.warnings);
+    let mut model_files = find_source_files(model_dir, &model_include, &filter, &mut run_report.skipped);
+
+    if text {
+        println!(
+            "Found {} Models in {}",
+            model_files.len(),
+            model_dir.display()
+        );
+    }
+
+    // Sails names models by identity, so a hook's model is only registered
+    // when neither the app nor an earlier hook has one by the same name
+    let model_identity = |js_file: &Path, _: &Path| {
+        sails_decl_core::model::model_identity(&js_file.file_stem().unwrap().to_string_lossy())
+    };
+    let hook_models = hooks.iter().map(|hook| (hook, hook.model_dir.as_path()));
+    let model_dirs = paths.model_dirs();
+    let hook_models = add_hook_files(&mut model_files, &model_dirs, hook_models, &model_include, &filter, &mut run_report, model_identity);
+    if text {
+        for (hook, found) in hook_models.into_iter().filter(|(_, found)| *found > 0) {
+            println!("Found {} Models in hook {}", found, hook.package);
+        }
+    }
     let js_files_count = model_files.len();
-    
-    println!(
-        "Found {} Models in {}",
-        js_files_count,
-        model_dir.display()
-    );
+
+    let mut project_config = sails_decl_core::config::ProjectConfig::load(project_root);
+    if *legacy {
+        project_config.sails_version = sails_decl_core::config::SailsVersion::Legacy;
+    }
+    project_config.strict_type_hints = *strict_type_hints;
+    for spec in type_imports {
+        match sails_decl_core::references::TypeImport::parse(spec, project_root) {
+            Some(import) => project_config.type_imports.push(import),
+            None => run_report
+                .warnings
+                .push(format!("Ignoring invalid --type-import '{}', expected Name=module", spec)),
+        }
+    }
+    for (sails_type, ts_type) in type_overrides {
+        if let Err(error) = project_config.types.add_override(sails_type, ts_type) {
+            run_report
+                .warnings
+                .push(format!("Ignoring invalid type override for '{}': {}", sails_type, error));
+        }
+    }
+    if let Some(nullability) = nullability {
+        project_config.types.nullability = (*nullability).into();
+    }
+    project_config.naming = naming.clone();
+    if text {
+        for warning in &run_report.warnings {
+            eprintln!("{}", warning);
+        }
+        report(path_diagnostics);
+        report(&project_config.diagnostics);
+    }
+    run_report.diagnostics = path_diagnostics.clone();
+    run_report.diagnostics.extend(project_config.diagnostics.iter().cloned());
+    let mut type_hint_failures = 0;
+
+    // Everything in the on-disk cache was generated by the same version with
+    // the same settings, or it is thrown away.
+    let cache_key = cache::hash_value(&(
+        env!("CARGO_PKG_VERSION"),
+        cache::hash_files(ProjectConfig::files(project_root).iter().map(PathBuf::as_path)),
+        legacy,
+        strict_type_hints,
+        type_imports,
+        nullability,
+        type_overrides,
+        naming,
+        model_dir,
+        helpers_dir,
+        types_dir,
+    ));
+    cache.disk.validate(cache_key);
+
+    // Models are parsed and emitted in parallel; collecting keeps them in
+    // file order, so the output does not depend on scheduling.
+    let parsed = &cache.models;
+    let cached = &cache.disk.models;
+    let load_results: Vec<_> = model_files
+        .into_par_iter()
+        .map(|js_file| {
+            let name = js_file.file_stem().unwrap().to_string_lossy().to_string();
+            let result = load_model(&js_file, &name, parsed, cached);
+            (js_file, name, result)
+        })
+        .collect();
+
+    let mut loaded_models = Vec::new();
+    let mut failed_models = Vec::new();
+    for (js_file, name, result) in load_results {
+        match result {
+            Ok(model) => {
+                if let LoadedModel::Parsed { model, .. } = &model {
+                    cache.models.insert(js_file.clone(), model.clone());
+                }
+                loaded_models.push((js_file, model));
+            }
+            Err(e) => failed_models.push((js_file, name, e)),
+        }
+    }
+    // Attributes shared between models are often kept next to them, and
+    // such a fragment is not a model of its own.
+    let required: HashSet<&PathBuf> = loaded_models
+        .iter()
+        .flat_map(|(_, model)| match model {
+            LoadedModel::Parsed { model, .. } => &model.dependencies,
+            LoadedModel::Cached(cached) => &cached.dependencies,
+        })
+        .collect();
+    for (js_file, name, e) in failed_models {
+        if required.contains(&js_file) {
+            run_report.skipped.push(SkippedFile {
+                path: js_file,
+                reason: "not a model, only required by other models",
+            });
+        } else {
+            run_report.models.push(FileReport {
+                source: js_file,
+                name,
+                status: Status::Failed,
+                output: None,
+                cached: false,
+                diagnostics: e.diagnostics,
+            });
+        }
+    }
+
+    // Associations and type hints can refer to any other model, so every
+    // model has to be indexed before any declaration is generated.
+    let mut model_index = sails_decl_core::model::ModelIndex::default();
+    let mut summaries = Vec::new();
+    for (js_file, model) in &loaded_models {
+        let declaration_path = models_types_dir
+            .join(relative_to(js_file, &model_dirs))
+            .with_extension("d.ts");
+        let summary = match model {
+            LoadedModel::Parsed { model, .. } => model.summary(&project_config),
+            LoadedModel::Cached(cached) => cached.summary.clone(),
+        };
+        model_index.insert_summary(&summary, &project_config, declaration_path);
+        summaries.push(summary);
+    }
+    let model_paths: Vec<&PathBuf> = loaded_models.iter().map(|(js_file, _)| js_file).collect();
+    let index_hash = cache::hash_value(&(model_paths, &summaries));
 
     let mut model_imports: Vec<sails_decl_core::helpers::ModelImport> = Vec::new();
+    // Nothing is written until everything has been generated, so `--check`
+    // can compare the whole set against what is on disk.
+    let mut outputs: Vec<OutputFile> = Vec::new();
 
-    for js_file in model_files {
-        let code = std::fs::read_to_string(&js_file).expect("Failed to read model file");
-        let name = js_file.file_stem().unwrap().to_string_lossy().to_string();
-        match sails_decl_core::model::gen_decl(code, name.clone(), Some(js_file.clone())) {
-            Ok(decl) => {
-                let model_type_name = sails_decl_core::model::model_type_name(&name);
-                let new_path = models_types_dir.join(js_file.strip_prefix(&model_dir).unwrap());
-                let declaration_path = new_path.with_extension("d.ts");
-                let decl_code = sails_decl_core::model::emit_with_source_map(decl, &declaration_path);
-                std::fs::create_dir_all(new_path.parent().unwrap()).expect("Failed to create directories for output file");
-                std::fs::write(&declaration_path, decl_code.code).expect("Failed to write declaration file");
-                std::fs::write(new_path.with_extension("d.ts.map"), decl_code.source_map).expect("Failed to write source map file");
+    let declarations: Vec<_> = loaded_models
+        .into_par_iter()
+        .zip(summaries)
+        .map(|((js_file, model), summary)| {
+            let name = summary.name.clone();
+            let new_path = models_types_dir.join(relative_to(&js_file, &model_dirs));
+            let declaration_path = new_path.with_extension("d.ts");
+            let result = declare_model(model, summary, &js_file, &declaration_path, &project_config, &model_index, index_hash);
+            (js_file, name, new_path, declaration_path, result)
+        })
+        .collect();
+
+    let mut cached_models = BTreeMap::new();
+    for (js_file, name, new_path, declaration_path, result) in declarations {
+        match result {
+            Ok((declared, from_cache)) => {
+                let model_type_name = project_config.naming.model_type(&name);
+                let source_map_path = new_path.with_extension("d.ts.map");
+                outputs.push(OutputFile::new(&declaration_path, declared.output.code.clone()));
+                outputs.push(OutputFile::new(&source_map_path, declared.output.source_map.clone()));
 
                 let mut import_path = declaration_path
-                    .strip_prefix(&types_dir)
+                    .strip_prefix(types_dir)
                     .unwrap_or(&declaration_path)
                     .to_string_lossy()
                     .replace('\\', "/");
@@ -131,44 +590,141 @@ fn run(
                 }
 
                 model_imports.push(sails_decl_core::helpers::ModelImport {
-                    model_name: name,
+                    model_name: name.clone(),
                     model_type_name,
                     import_path,
                 });
+                run_report.models.push(FileReport {
+                    source: js_file.clone(),
+                    name,
+                    status: Status::Generated,
+                    output: Some(OutputPaths {
+                        declaration: declaration_path,
+                        source_map: source_map_path,
+                    }),
+                    cached: from_cache,
+                    diagnostics: declared.output.diagnostics.clone(),
+                });
+                cached_models.insert(js_file, declared);
+            }
+            Err(e) => {
+                if matches!(e.error, sails_decl_core::model::GenDeclarationsError::SDTypeHintParseError) {
+                    type_hint_failures += 1;
+                }
+                run_report.models.push(FileReport {
+                    source: js_file,
+                    name,
+                    status: Status::Failed,
+                    output: None,
+                    cached: false,
+                    diagnostics: e.diagnostics,
+                });
             }
-            Err(e) => eprintln!("Error processing {}: {:?}", js_file.display(), e),
+        }
+    }
+    // Replacing the entries also drops the ones for deleted models
+    cache.disk.models = cached_models;
+    if text {
+        for model in &run_report.models {
+            report(&model.diagnostics);
         }
     }
 
     let total_duration = models_start.elapsed();
+    run_report.timings.models_ms = total_duration.as_millis();
 
-    println!(
-        "Processed {} models in {} ms",
-        js_files_count,
-        total_duration.as_millis()
-    );
+    if text {
+        println!(
+            "Processed {} models in {} ms",
+            js_files_count,
+            total_duration.as_millis()
+        );
+    }
 
     let helpers_start = Instant::now();
 
-    let helper_files = glob::glob(&format!("{}/**/*.js", helpers_dir.display()))
-        .expect("Failed to read glob pattern")
-        .filter_map(Result::ok)
-        .filter(|path| {
-            !ignored_files
-                .iter()
-                .any(|ignored| path.starts_with(ignored))
-        })
-        .collect::<Vec<_>>();
+    let mut helper_files = find_source_files(helpers_dir, &helpers_include, &filter, &mut run_report.skipped);
+    let hook_helpers = hooks.iter().map(|hook| (hook, hook.helpers_dir.as_path()));
+    let helper_dirs = paths.helper_dirs();
+    let hook_helpers = add_hook_files(&mut helper_files, &helper_dirs, hook_helpers, &helpers_include, &filter, &mut run_report, helper_name);
+    if text {
+        for (hook, found) in hook_helpers.into_iter().filter(|(_, found)| *found > 0) {
+            println!("Found {} Helpers in hook {}", found, hook.package);
+        }
+    }
 
     let helpers_out = types_dir.join("helpers.d.ts");
-    let emitted_helpers = sails_decl_core::helpers::generate_sails_helpers(&helper_files, &helpers_dir, &helpers_out);
-    std::fs::write(&helpers_out, emitted_helpers.code).expect("Failed to write helpers declaration file");
     let helpers_map_out = helpers_out.with_file_name(format!(
         "{}.map",
         helpers_out.file_name().unwrap().to_string_lossy()
     ));
-    std::fs::write(helpers_map_out, emitted_helpers.source_map)
-        .expect("Failed to write helpers source map file");
+    // All helpers go into one file, so it is reused only if none of them changed
+    let hash_helpers = |dependencies: &[PathBuf]| {
+        cache::hash_files(helper_files.iter().chain(dependencies).map(PathBuf::as_path))
+    };
+    let reused_helpers = cache.disk.helpers.take().filter(|cached| {
+        cached.sources == helper_files
+            && cached.index_hash == index_hash
+            && cached.source_hash == hash_helpers(&cached.dependencies)
+    });
+    let helpers_from_cache = reused_helpers.is_some();
+    let declared_helpers = match reused_helpers {
+        Some(cached) => Ok(cached),
+        None => generate_sails_helpers_cached(&helper_files, &helper_dirs, &helpers_out, &project_config, &model_index, &mut cache.helpers)
+            .map(|generated| CachedHelpers {
+                sources: helper_files.clone(),
+                source_hash: hash_helpers(&generated.dependencies),
+                dependencies: generated.dependencies,
+                index_hash,
+                failed: generated.failed,
+                output: CachedOutput {
+                    code: generated.emitted.code,
+                    source_map: generated.emitted.source_map,
+                    diagnostics: generated.emitted.diagnostics,
+                },
+            }),
+    };
+    let (failed_helpers, helper_diagnostics, helpers_output) =
+        match declared_helpers {
+            Ok(declared) => {
+                outputs.push(OutputFile::new(&helpers_out, declared.output.code.clone()));
+                outputs.push(OutputFile::new(&helpers_map_out, declared.output.source_map.clone()));
+                let output = OutputPaths {
+                    declaration: helpers_out.clone(),
+                    source_map: helpers_map_out.clone(),
+                };
+                let result = (declared.failed.clone(), declared.output.diagnostics.clone(), Some(output));
+                cache.disk.helpers = Some(declared);
+                result
+            }
+            Err(e) => {
+                type_hint_failures += 1;
+                (helper_files.clone(), e.diagnostics, None)
+            }
+        };
+    if text {
+        report(&helper_diagnostics);
+    }
+
+    // Helpers all go into one declaration file, so split its diagnostics
+    // back up by the file they were found in.
+    let mut helper_diagnostics = helper_diagnostics;
+    for helper_file in &helper_files {
+        let (diagnostics, rest) = helper_diagnostics
+            .into_iter()
+            .partition(|diagnostic| diagnostic.file.as_ref() == Some(helper_file));
+        helper_diagnostics = rest;
+        let failed = failed_helpers.contains(helper_file);
+        run_report.helpers.push(FileReport {
+            source: helper_file.clone(),
+            name: helper_name(helper_file, base_dir(helper_file, &helper_dirs)),
+            status: if failed { Status::Failed } else { Status::Generated },
+            output: if failed { None } else { helpers_output.clone() },
+            cached: helpers_from_cache && !failed,
+            diagnostics,
+        });
+    }
+    run_report.diagnostics.extend(helper_diagnostics);
 
     let global_out = types_dir.join("global.d.ts");
     let emitted_global = sails_decl_core::helpers::generate_global_declarations_file(
@@ -176,19 +732,260 @@ fn run(
         "./helpers",
         &global_out,
     );
-    std::fs::write(&global_out, emitted_global.code).expect("Failed to write global declaration file");
     let global_map_out = global_out.with_file_name(format!(
         "{}.map",
         global_out.file_name().unwrap().to_string_lossy()
     ));
-    std::fs::write(global_map_out, emitted_global.source_map)
-        .expect("Failed to write global source map file");
+    outputs.push(OutputFile::new(&global_out, emitted_global.code));
+    outputs.push(OutputFile::new(&global_map_out, emitted_global.source_map));
+    run_report.global_declaration = Some(OutputPaths {
+        declaration: global_out,
+        source_map: global_map_out,
+    });
 
     let helpers_duration = helpers_start.elapsed();
+    run_report.timings.helpers_ms = helpers_duration.as_millis();
+    run_report.timings.total_ms = run_start.elapsed().as_millis();
+
+    if text {
+        println!(
+            "Processed {} helpers in {} ms",
+            helper_files.len(),
+            helpers_duration.as_millis()
+        );
+    }
+
+    Generation {
+        report: run_report,
+        outputs,
+        type_hint_failures,
+    }
+}
+
+/// A model ready to be indexed, either parsed or summarized by the disk cache.
+enum LoadedModel {
+    Parsed { model: ParsedModel, source_hash: u64 },
+    Cached(CachedModel),
+}
+
+fn load_model(
+    js_file: &Path,
+    name: &str,
+    parsed: &HashMap<PathBuf, ParsedModel>,
+    cached: &BTreeMap<PathBuf, CachedModel>,
+) -> Result<LoadedModel, Diagnosed<GenDeclarationsError>> {
+    if let Some(cached) = cached.get(js_file)
+        && cached.source_hash == hash_sources(js_file, &cached.dependencies)
+    {
+        return Ok(LoadedModel::Cached(cached.clone()));
+    }
+    let model = match parsed.get(js_file) {
+        Some(model) => model.clone(),
+        None => {
+            let code = std::fs::read_to_string(js_file).expect("Failed to read model file");
+            sails_decl_core::model::parse_model(code, name.to_string(), Some(js_file.to_path_buf()))?
+        }
+    };
+    let source_hash = hash_sources(js_file, &model.dependencies);
+    Ok(LoadedModel::Parsed { model, source_hash })
+}
+
+/// Generates the declaration for `model`, unless the cached one was
+/// generated against the same model index. Returns whether it was cached.
+fn declare_model(
+    model: LoadedModel,
+    summary: ModelSummary,
+    js_file: &Path,
+    declaration_path: &Path,
+    project_config: &ProjectConfig,
+    model_index: &ModelIndex,
+    index_hash: u64,
+) -> Result<(CachedModel, bool), Diagnosed<GenDeclarationsError>> {
+    let (model, source_hash) = match model {
+        LoadedModel::Cached(cached) if cached.index_hash == index_hash => return Ok((cached, true)),
+        LoadedModel::Cached(cached) => {
+            let code = std::fs::read_to_string(js_file).expect("Failed to read model file");
+            let model = sails_decl_core::model::parse_model(code, summary.name.clone(), Some(js_file.to_path_buf()))?;
+            (model, cached.source_hash)
+        }
+        LoadedModel::Parsed { model, source_hash } => (model, source_hash),
+    };
+    let dependencies = model.dependencies.clone();
+    let decl = sails_decl_core::model::gen_decl(model, project_config, model_index, declaration_path)?;
+    let emitted = sails_decl_core::model::emit_with_source_map(decl, declaration_path);
+    let declared = CachedModel {
+        source_hash,
+        dependencies,
+        summary,
+        index_hash,
+        output: CachedOutput {
+            code: emitted.code,
+            source_map: emitted.source_map,
+            diagnostics: emitted.diagnostics,
+        },
+    };
+    Ok((declared, false))
+}
+
+fn hash_sources(file: &Path, dependencies: &[PathBuf]) -> u64 {
+    cache::hash_files(std::iter::once(file).chain(dependencies.iter().map(PathBuf::as_path)))
+}
+
+/// A generated file, held in memory until it is written or checked.
+struct OutputFile {
+    path: PathBuf,
+    contents: String,
+}
+
+impl OutputFile {
+    fn new(path: &Path, contents: String) -> Self {
+        OutputFile {
+            path: path.to_path_buf(),
+            contents,
+        }
+    }
+
+    /// Writes the file unless it already has these contents, so unchanged
+    /// declarations are not touched. Returns whether it was written.
+    fn write(&self) -> bool {
+        if std::fs::read_to_string(&self.path).is_ok_and(|on_disk| on_disk == self.contents) {
+            return false;
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create directories for output file");
+        }
+        std::fs::write(&self.path, &self.contents)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {}", self.path.display(), e));
+        true
+    }
+
+    /// A unified diff from the file on disk to the generated contents, or
+    /// `None` if they are the same. A missing file is diffed as empty.
+    fn diff_with_disk(&self, project_root: &Path) -> Option<String> {
+        let on_disk = std::fs::read_to_string(&self.path).ok();
+        if on_disk.as_deref() == Some(self.contents.as_str()) {
+            return None;
+        }
+        Some(unified_diff(&self.path, project_root, on_disk.as_deref(), Some(&self.contents)))
+    }
+}
+
+/// A unified diff of `path` going from `old` to `new`, where `None` means the
+/// file does not exist.
+fn unified_diff(path: &Path, project_root: &Path, old: Option<&str>, new: Option<&str>) -> String {
+    let path = path.strip_prefix(project_root).unwrap_or(path).display().to_string();
+    let header = |contents: Option<&str>, side: &str| match contents {
+        Some(_) => format!("{}/{}", side, path),
+        None => "/dev/null".to_string(),
+    };
+    similar::TextDiff::from_lines(old.unwrap_or_default(), new.unwrap_or_default())
+        .unified_diff()
+        .header(&header(old, "a"), &header(new, "b"))
+        .missing_newline_hint(true)
+        .to_string()
+}
+
+/// The manifest for `outputs`, and the files earlier runs generated for
+/// models that are gone.
+fn find_orphans(paths: &ProjectPaths, outputs: &[OutputFile], report: &Report) -> (Manifest, Vec<Orphan>) {
+    let mut manifest = Manifest::new(&paths.types_dir, outputs, report);
+    let orphans = manifest.inherit(Manifest::load(&paths.types_dir), report, &paths.types_dir);
+    (manifest, orphans)
+}
+
+/// Removes the files earlier runs generated for models that are gone, and
+/// records what this run generated. Orphans that were edited since they were
+/// generated are left in place with a warning.
+fn remove_orphans(paths: &ProjectPaths, outputs: &[OutputFile], report: &mut Report, text: bool) {
+    let (manifest, orphans) = find_orphans(paths, outputs, report);
+    for orphan in orphans {
+        let display = orphan.path.strip_prefix(&paths.root).unwrap_or(&orphan.path).display().to_string();
+        if orphan.generated_contents().is_some() {
+            orphan.remove(&paths.types_dir);
+            if text {
+                println!("Removed {}", display);
+            }
+            report.removed.push(orphan.path);
+        } else if orphan.path.exists() {
+            let warning = format!("Keeping {}, it was edited after being generated for a model that no longer exists", display);
+            if text {
+                eprintln!("{}", warning);
+            }
+            report.warnings.push(warning);
+        }
+    }
+    manifest.save(&paths.types_dir);
+}
+
+/// Adds the sources in each hook's directory to `files`, skipping the ones
+/// named like a file that is already there, which Sails lets the app
+/// override. `dirs` are the directories of all of them. Returns how many
+/// files each hook added.
+fn add_hook_files<'a>(
+    files: &mut Vec<PathBuf>,
+    dirs: &[PathBuf],
+    hook_dirs: impl Iterator<Item = (&'a InstalledHook, &'a Path)>,
+    include: &GlobSet,
+    filter: &FileFilter<'_>,
+    run_report: &mut Report,
+    name: impl Fn(&Path, &Path) -> String,
+) -> Vec<(&'a InstalledHook, usize)> {
+    let mut names: HashSet<String> = files.iter().map(|file| name(file, base_dir(file, dirs))).collect();
+    // A hook's own .gitignore is about its repository, not what it publishes
+    let filter = FileFilter {
+        project_root: filter.project_root,
+        ignored_files: filter.ignored_files,
+        exclude: filter.exclude.clone(),
+        respect_ignore_files: false,
+    };
+    let mut added = Vec::new();
+    for (hook, dir) in hook_dirs {
+        let mut found = 0;
+        for file in find_source_files(dir, include, &filter, &mut run_report.skipped) {
+            if names.insert(name(&file, dir)) {
+                files.push(file);
+                found += 1;
+            } else {
+                run_report.skipped.push(SkippedFile {
+                    path: file,
+                    reason: "overridden by the app or an earlier hook",
+                });
+            }
+        }
+        added.push((hook, found));
+    }
+    added
+}
+
+/// The first of `dirs` that `path` is in, or else the first of them.
+fn base_dir<'a>(path: &Path, dirs: &'a [PathBuf]) -> &'a Path {
+    dirs.iter().find(|dir| path.starts_with(dir)).unwrap_or(&dirs[0])
+}
+
+/// `path` relative to the first of `dirs` it is in, which it was found in.
+fn relative_to<'a>(path: &'a Path, dirs: &[PathBuf]) -> &'a Path {
+    path.strip_prefix(base_dir(path, dirs)).unwrap()
+}
+
+/// The name a helper is called by, e.g. `user/get-name` for `api/helpers/user/get-name.js`.
+fn helper_name(path: &Path, helpers_dir: &Path) -> String {
+    let relative = path
+        .strip_prefix(helpers_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/");
+    sails_decl_core::source::strip_extension(&relative).to_string()
+}
+
+fn report(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+}
 
+fn print_json(run_report: &Report) {
     println!(
-        "Processed {} helpers in {} ms",
-        helper_files.len(),
-        helpers_duration.as_millis()
+        "{}",
+        serde_json::to_string_pretty(run_report).expect("Failed to serialize report")
     );
 }
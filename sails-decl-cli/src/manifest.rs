@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::OutputFile;
+use crate::report::Report;
+
+/// Where the manifest is kept, inside the types directory.
+pub const FILE_NAME: &str = ".sails-decl-manifest.json";
+
+/// The files earlier runs generated in the types directory, so the ones that
+/// are no longer generated can be removed without touching anything else.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Manifest {
+    /// Keyed by path relative to the types directory.
+    files: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ManifestEntry {
+    /// The model the file was generated from. Files without one, like
+    /// `helpers.d.ts`, are generated by every run and never orphaned.
+    source: Option<PathBuf>,
+    /// Hash of the contents as generated, to tell whether it was edited since.
+    hash: u64,
+}
+
+/// A file an earlier run generated from a model that is gone.
+pub struct Orphan {
+    pub path: PathBuf,
+    hash: u64,
+}
+
+impl Manifest {
+    /// Reads the manifest in `types_dir`. A missing or unreadable manifest is
+    /// empty, so nothing is removed until a run has recorded what it owns.
+    pub fn load(types_dir: &Path) -> Self {
+        std::fs::read_to_string(types_dir.join(FILE_NAME))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, types_dir: &Path) {
+        let json = serde_json::to_string_pretty(self).expect("Failed to serialize manifest");
+        std::fs::create_dir_all(types_dir).expect("Failed to create types directory");
+        std::fs::write(types_dir.join(FILE_NAME), json).expect("Failed to write manifest file");
+    }
+
+    /// Records `outputs`, attributing each model's declarations to its source
+    /// as listed in `report`.
+    pub fn new(types_dir: &Path, outputs: &[OutputFile], report: &Report) -> Self {
+        let mut sources = BTreeMap::new();
+        for model in &report.models {
+            if let Some(output) = &model.output {
+                sources.insert(&output.declaration, &model.source);
+                sources.insert(&output.source_map, &model.source);
+            }
+        }
+        let files = outputs
+            .iter()
+            .filter_map(|output| {
+                let relative = output.path.strip_prefix(types_dir).ok()?;
+                let entry = ManifestEntry {
+                    source: sources.get(&output.path).map(|source| source.to_path_buf()),
+                    hash: xxh3_64(output.contents.as_bytes()),
+                };
+                Some((relative.to_path_buf(), entry))
+            })
+            .collect();
+        Manifest { files }
+    }
+
+    /// Takes over what `previous` recorded that this run did not generate.
+    /// Files of models that still exist, but failed this time, are kept;
+    /// the ones left over are returned.
+    pub fn inherit(&mut self, previous: Manifest, report: &Report, types_dir: &Path) -> Vec<Orphan> {
+        let live: HashSet<&Path> = report.models.iter().map(|model| model.source.as_path()).collect();
+        let mut orphans = Vec::new();
+        for (relative, entry) in previous.files {
+            if self.files.contains_key(&relative) || !is_inside(&relative) {
+                continue;
+            }
+            match &entry.source {
+                Some(source) if !live.contains(source.as_path()) => orphans.push(Orphan {
+                    path: types_dir.join(&relative),
+                    hash: entry.hash,
+                }),
+                _ => {
+                    self.files.insert(relative, entry);
+                }
+            }
+        }
+        orphans
+    }
+}
+
+impl Orphan {
+    /// The contents on disk, if the file still exists and is what was
+    /// generated. Anything else was edited by hand and is left alone.
+    pub fn generated_contents(&self) -> Option<String> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .filter(|contents| xxh3_64(contents.as_bytes()) == self.hash)
+    }
+
+    /// Deletes the file, along with any directories under `types_dir` it
+    /// leaves empty.
+    pub fn remove(&self, types_dir: &Path) {
+        std::fs::remove_file(&self.path)
+            .unwrap_or_else(|e| panic!("Failed to remove {}: {}", self.path.display(), e));
+        let mut dir = self.path.parent();
+        while let Some(parent) = dir.filter(|parent| parent.starts_with(types_dir) && *parent != types_dir) {
+            if std::fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+}
+
+/// Guards against a tampered manifest pointing outside the types directory.
+fn is_inside(relative: &Path) -> bool {
+    relative.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{FileReport, OutputPaths, Status};
+
+    /// An empty types directory, unique to `name`.
+    fn types_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sails-decl-manifest-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes the declarations of the model at `source` to `types_dir`, as a
+    /// run would, and returns them with the report on the model.
+    fn generate(types_dir: &Path, source: &str) -> (Vec<OutputFile>, FileReport) {
+        let name = Path::new(source).file_stem().unwrap().to_string_lossy().to_string();
+        let declaration = types_dir.join("models").join(format!("{}.d.ts", name));
+        let source_map = types_dir.join("models").join(format!("{}.d.ts.map", name));
+        let outputs = vec![
+            OutputFile::new(&declaration, format!("export declare interface {}__ModelDecl {{}}\n", name)),
+            OutputFile::new(&source_map, "{}".to_string()),
+        ];
+        for output in &outputs {
+            output.write();
+        }
+        let model = FileReport {
+            source: PathBuf::from(source),
+            name,
+            status: Status::Generated,
+            output: Some(OutputPaths {
+                declaration,
+                source_map,
+            }),
+            cached: false,
+            diagnostics: vec![],
+        };
+        (outputs, model)
+    }
+
+    fn report(models: Vec<FileReport>) -> Report {
+        Report {
+            models,
+            ..Report::default()
+        }
+    }
+
+    #[test]
+    fn renamed_model_is_removed() {
+        let types_dir = types_dir("renamed");
+        let (outputs, model) = generate(&types_dir, "/app/api/models/Pet.js");
+        Manifest::new(&types_dir, &outputs, &report(vec![model])).save(&types_dir);
+
+        let (outputs, model) = generate(&types_dir, "/app/api/models/Animal.js");
+        let report = report(vec![model]);
+        let mut manifest = Manifest::new(&types_dir, &outputs, &report);
+        let orphans = manifest.inherit(Manifest::load(&types_dir), &report, &types_dir);
+
+        let mut paths: Vec<&Path> = orphans.iter().map(|orphan| orphan.path.as_path()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [types_dir.join("models/Pet.d.ts"), types_dir.join("models/Pet.d.ts.map")]
+        );
+        for orphan in &orphans {
+            assert!(orphan.generated_contents().is_some());
+            orphan.remove(&types_dir);
+            assert!(!orphan.path.exists());
+        }
+        assert!(types_dir.join("models/Animal.d.ts").exists());
+        std::fs::remove_dir_all(&types_dir).unwrap();
+    }
+
+    #[test]
+    fn edited_orphan_is_kept() {
+        let types_dir = types_dir("edited");
+        let (outputs, model) = generate(&types_dir, "/app/api/models/Pet.js");
+        Manifest::new(&types_dir, &outputs, &report(vec![model])).save(&types_dir);
+        std::fs::write(types_dir.join("models/Pet.d.ts"), "// edited by hand\n").unwrap();
+
+        let report = report(vec![]);
+        let mut manifest = Manifest::new(&types_dir, &[], &report);
+        let orphans = manifest.inherit(Manifest::load(&types_dir), &report, &types_dir);
+
+        let edited = orphans.iter().find(|orphan| orphan.path.ends_with("Pet.d.ts")).unwrap();
+        assert_eq!(edited.generated_contents(), None);
+        std::fs::remove_dir_all(&types_dir).unwrap();
+    }
+
+    #[test]
+    fn failed_model_keeps_its_outputs() {
+        let types_dir = types_dir("failed");
+        let (outputs, model) = generate(&types_dir, "/app/api/models/Pet.js");
+        Manifest::new(&types_dir, &outputs, &report(vec![model])).save(&types_dir);
+
+        let report = report(vec![FileReport {
+            source: PathBuf::from("/app/api/models/Pet.js"),
+            name: "Pet".to_string(),
+            status: Status::Failed,
+            output: None,
+            cached: false,
+            diagnostics: vec![],
+        }]);
+        let mut manifest = Manifest::new(&types_dir, &[], &report);
+        let orphans = manifest.inherit(Manifest::load(&types_dir), &report, &types_dir);
+
+        assert!(orphans.is_empty());
+        // Still recorded, so they are removed once the model is gone
+        assert!(manifest.files.contains_key(Path::new("models/Pet.d.ts")));
+        assert!(manifest.files.contains_key(Path::new("models/Pet.d.ts.map")));
+        std::fs::remove_dir_all(&types_dir).unwrap();
+    }
+
+    #[test]
+    fn paths_outside_the_types_dir_are_ignored() {
+        let types_dir = types_dir("tampered");
+        let tampered = format!(
+            r#"{{"files": {{"../x": {{"source": "/app/api/models/Gone.js", "hash": {}}}}}}}"#,
+            xxh3_64(b"")
+        );
+        std::fs::write(types_dir.join(FILE_NAME), tampered).unwrap();
+
+        let report = report(vec![]);
+        let mut manifest = Manifest::new(&types_dir, &[], &report);
+        let orphans = manifest.inherit(Manifest::load(&types_dir), &report, &types_dir);
+
+        assert!(orphans.is_empty());
+        assert!(manifest.files.is_empty());
+        std::fs::remove_dir_all(&types_dir).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use sails_decl_core::diagnostics::Diagnostic;
+use serde::Serialize;
+
+/// Everything a `run` did, printed as JSON with `--format json`.
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub project_root: PathBuf,
+    pub success: bool,
+    /// Why the run stopped before processing anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub models: Vec<FileReport>,
+    pub helpers: Vec<FileReport>,
+    pub global_declaration: Option<OutputPaths>,
+    pub skipped: Vec<SkippedFile>,
+    /// With `--check`, the generated files that differ from the ones on disk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale: Option<Vec<PathBuf>>,
+    /// Files earlier runs generated for models that no longer exist, which
+    /// were deleted.
+    pub removed: Vec<PathBuf>,
+    /// Problems with the project config files.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Problems with the command line options.
+    pub warnings: Vec<String>,
+    pub timings: Timings,
+}
+
+impl Report {
+    /// How many models and helpers could not be declared.
+    pub fn failed_files(&self) -> usize {
+        self.models
+            .iter()
+            .chain(&self.helpers)
+            .filter(|file| file.status == Status::Failed)
+            .count()
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Generated,
+    Failed,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReport {
+    pub source: PathBuf,
+    pub name: String,
+    pub status: Status,
+    pub output: Option<OutputPaths>,
+    /// Whether the output was reused from an earlier run.
+    pub cached: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputPaths {
+    pub declaration: PathBuf,
+    pub source_map: PathBuf,
+}
+
+#[derive(Serialize)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: &'static str,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Timings {
+    pub models_ms: u128,
+    pub helpers_ms: u128,
+    pub total_ms: u128,
+}
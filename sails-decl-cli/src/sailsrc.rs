@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use sails_decl_core::config::SailsPaths;
+use serde::Deserialize;
+
+/// The part of `.sailsrc` that says where Sails looks for files.
+#[derive(Deserialize, Default)]
+struct Sailsrc {
+    #[serde(default)]
+    paths: Paths,
+}
+
+#[derive(Deserialize, Default)]
+struct Paths {
+    models: Option<String>,
+    helpers: Option<String>,
+}
+
+/// Reads the `paths` setting from `.sailsrc`. Like the `rc` package Sails
+/// loads it with, the file is JSON with comments if it starts with `{`, and
+/// INI otherwise.
+pub fn read_paths(path: &Path) -> Result<SailsPaths, String> {
+    let error = |e: &dyn std::fmt::Display| format!("Failed to read {}: {}", path.display(), e);
+    let contents = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+    let sailsrc = match contents.trim_start().starts_with('{') {
+        true => serde_json::from_str(&strip_comments(&contents)).map_err(|e| error(&e))?,
+        false => parse_ini(&contents),
+    };
+    Ok(SailsPaths {
+        models: sailsrc.paths.models,
+        helpers: sailsrc.paths.helpers,
+        diagnostics: vec![],
+    })
+}
+
+/// Blanks out `//` and `/* */` comments, keeping line numbers for errors.
+fn strip_comments(json: &str) -> String {
+    let mut stripped = String::with_capacity(json.len());
+    let mut chars = json.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => stripped.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(c);
+            }
+            ('/', Some('/')) => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        stripped.push(c);
+                    }
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Reads the keys of the `[paths]` section.
+fn parse_ini(ini: &str) -> Sailsrc {
+    let mut sailsrc = Sailsrc::default();
+    let mut section = String::new();
+    for line in ini.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')))
+            .unwrap_or(value)
+            .to_string();
+        match (section.as_str(), key.trim()) {
+            ("paths", "models") => sailsrc.paths.models = Some(value),
+            ("paths", "helpers") => sailsrc.paths.helpers = Some(value),
+            _ => {}
+        }
+    }
+    sailsrc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment_markers_inside_strings_are_kept() {
+        let json = r#"{ "paths": { "models": "api//models/*x*/", "helpers": "a\"//b" } } // trailing"#;
+        let sailsrc: Sailsrc = serde_json::from_str(&strip_comments(json)).unwrap();
+        assert_eq!(sailsrc.paths.models.as_deref(), Some("api//models/*x*/"));
+        assert_eq!(sailsrc.paths.helpers.as_deref(), Some("a\"//b"));
+    }
+
+    #[test]
+    fn block_comments_are_removed_keeping_lines() {
+        let json = "{\n  /* where\n     models are */ \"paths\": { \"models\": \"lib/models\" /**/ }\n}";
+        let stripped = strip_comments(json);
+        assert_eq!(stripped.lines().count(), json.lines().count());
+        let sailsrc: Sailsrc = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(sailsrc.paths.models.as_deref(), Some("lib/models"));
+    }
+
+    #[test]
+    fn quoted_ini_values_are_unquoted() {
+        let sailsrc = parse_ini("[paths]\nmodels = \"lib/models\"\nhelpers='lib/helpers'\n");
+        assert_eq!(sailsrc.paths.models.as_deref(), Some("lib/models"));
+        assert_eq!(sailsrc.paths.helpers.as_deref(), Some("lib/helpers"));
+    }
+
+    #[test]
+    fn ini_without_paths_section_sets_nothing() {
+        let sailsrc = parse_ini("; generators\nmodels = lib/models\n[generators]\nhelpers = lib/helpers\n");
+        assert_eq!(sailsrc.paths.models, None);
+        assert_eq!(sailsrc.paths.helpers, None);
+    }
+}
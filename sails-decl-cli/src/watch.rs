@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{Debouncer, RecommendedCache, new_debouncer};
+
+use crate::{
+    Cache, Format, Generation, ProjectPaths, RunArgs, config, exit_with_error, generate, load_project, print_error,
+    print_failed_files, print_json, remove_orphans,
+};
+
+/// Editors often save a file in several steps, and "save all" touches many
+/// files at once, so changes are batched until things have been quiet this long.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub fn watch(cli_args: &RunArgs) {
+    let (mut args, mut paths) = load_project(cli_args).unwrap_or_else(|error| exit_with_error(cli_args, error));
+    if args.check {
+        eprintln!("Error: --check cannot be used with watch");
+        std::process::exit(1);
+    }
+    let mut cache = Cache::load(&args, &paths);
+    regenerate(&args, &paths, &mut cache);
+
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, None, tx).expect("Failed to start file watcher");
+    let mut watched = Watched::new(cli_args, &paths, &cache);
+    let mut subscriptions = BTreeMap::new();
+    subscribe(&mut debouncer, &mut subscriptions, &watched);
+    if args.format == Format::Text {
+        println!("Watching for changes, press Ctrl+C to stop");
+    }
+
+    for events in rx {
+        let events = match events {
+            Ok(events) => events,
+            Err(errors) => {
+                for e in errors {
+                    eprintln!("Error: failed to watch for changes: {}", e);
+                }
+                continue;
+            }
+        };
+        let mut changed = false;
+        let mut reload = false;
+        // Generating reads the watched files, which shows up as access events
+        for event in events.iter().filter(|event| !matches!(event.kind, EventKind::Access(_))) {
+            // The declarations may be written inside a watched directory
+            for path in event
+                .paths
+                .iter()
+                .filter(|path| !path.starts_with(&paths.types_dir) && watched.contains(path))
+            {
+                if watched.is_setting(path) {
+                    reload = true;
+                } else {
+                    invalidate(&mut cache, &paths, path);
+                }
+                changed = true;
+            }
+        }
+        if reload {
+            // Until the settings are valid again, the old ones are kept
+            match load_project(cli_args) {
+                Ok((new_args, new_paths)) => {
+                    (args, paths) = (new_args, new_paths);
+                    cache = Cache::load(&args, &paths);
+                }
+                Err(error) => {
+                    print_error(&args, error);
+                    continue;
+                }
+            }
+        }
+        if changed {
+            regenerate(&args, &paths, &mut cache);
+            // Hooks, directories and required files may have come or gone
+            watched = Watched::new(cli_args, &paths, &cache);
+            subscribe(&mut debouncer, &mut subscriptions, &watched);
+        }
+    }
+}
+
+/// The files whose changes can change the declarations.
+struct Watched {
+    /// The model and helpers directories, whose whole contents matter.
+    source_dirs: Vec<PathBuf>,
+    config_dir: PathBuf,
+    /// Files that decide the settings and where sources are found.
+    settings: BTreeSet<PathBuf>,
+    /// Files outside the source directories that models and helpers `require()`.
+    dependencies: BTreeSet<PathBuf>,
+}
+
+impl Watched {
+    fn new(cli_args: &RunArgs, paths: &ProjectPaths, cache: &Cache) -> Self {
+        let source_dirs: Vec<PathBuf> = paths.model_dirs().into_iter().chain(paths.helper_dirs()).collect();
+        let config_file = match &cli_args.config {
+            Some(path) => std::path::absolute(path).unwrap_or_else(|_| path.clone()),
+            None => paths.root.join(config::FILE_NAME),
+        };
+        let dependencies = cache
+            .disk
+            .models
+            .values()
+            .flat_map(|model| &model.dependencies)
+            .chain(cache.disk.helpers.iter().flat_map(|helpers| &helpers.dependencies))
+            .filter(|path| !source_dirs.iter().any(|dir| path.starts_with(dir)))
+            .cloned()
+            .collect();
+        Watched {
+            source_dirs,
+            config_dir: paths.root.join("config"),
+            settings: [config_file, paths.root.join("package.json"), paths.root.join(".sailsrc")].into(),
+            dependencies,
+        }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.source_dirs.iter().any(|dir| path.starts_with(dir))
+            || self.is_setting(path)
+            || self.dependencies.contains(path)
+    }
+
+    /// Whether `path` can change the settings or the directories they point
+    /// at, as `config/paths.js` can.
+    fn is_setting(&self, path: &Path) -> bool {
+        self.settings.contains(path) || path.starts_with(&self.config_dir)
+    }
+
+    /// The directories to subscribe to. Single files are watched through
+    /// their directory, since editors often save by replacing the file.
+    fn subscriptions(&self) -> BTreeMap<PathBuf, RecursiveMode> {
+        let dirs: Vec<&PathBuf> = self.source_dirs.iter().chain([&self.config_dir]).collect();
+        let mut subscriptions: BTreeMap<PathBuf, RecursiveMode> = dirs
+            .iter()
+            .filter(|dir| dir.is_dir())
+            .map(|dir| (dir.to_path_buf(), RecursiveMode::Recursive))
+            .collect();
+        for file in self.settings.iter().chain(&self.dependencies) {
+            if let Some(dir) = file.parent()
+                && dir.is_dir()
+                && !dirs.iter().any(|watched| dir.starts_with(watched))
+            {
+                subscriptions.entry(dir.to_path_buf()).or_insert(RecursiveMode::NonRecursive);
+            }
+        }
+        subscriptions
+    }
+}
+
+/// Brings what `debouncer` watches in line with `watched`.
+fn subscribe(
+    debouncer: &mut Debouncer<RecommendedWatcher, RecommendedCache>,
+    subscriptions: &mut BTreeMap<PathBuf, RecursiveMode>,
+    watched: &Watched,
+) {
+    let wanted = watched.subscriptions();
+    for (dir, mode) in subscriptions.iter() {
+        if wanted.get(dir) != Some(mode) {
+            // Fails if the directory is gone already, which is fine
+            let _ = debouncer.unwatch(dir);
+        }
+    }
+    for (dir, mode) in &wanted {
+        if subscriptions.get(dir) != Some(mode) {
+            debouncer
+                .watch(dir, *mode)
+                .unwrap_or_else(|e| panic!("Failed to watch {}: {}", dir.display(), e));
+        }
+    }
+    *subscriptions = wanted;
+}
+
+/// Drops whatever `path` may have changed from the cache.
+fn invalidate(cache: &mut Cache, paths: &ProjectPaths, path: &Path) {
+    if paths.model_dirs().iter().any(|dir| path.starts_with(dir)) {
+        // Anything but a model is a file that models may `require()`
+        if cache.models.remove(path).is_none() {
+            cache.models.clear();
+        }
+    } else if paths.helper_dirs().iter().any(|dir| path.starts_with(dir)) {
+        if !cache.helpers.invalidate(path) {
+            cache.helpers.clear();
+        }
+    } else {
+        // A file required from elsewhere. The on-disk cache notices the
+        // change by its hash, but the sources parsed with it are stale.
+        cache.models.retain(|_, model| !model.dependencies.iter().any(|dependency| dependency == path));
+        if cache
+            .disk
+            .helpers
+            .as_ref()
+            .is_some_and(|helpers| helpers.dependencies.iter().any(|dependency| dependency == path))
+        {
+            cache.helpers.clear();
+        }
+    }
+}
+
+/// Generates everything from the cache, writing only the files that changed.
+fn regenerate(args: &RunArgs, paths: &ProjectPaths, cache: &mut Cache) {
+    let Generation {
+        mut report,
+        outputs,
+        type_hint_failures,
+    } = generate(args, paths, cache);
+
+    let mut written = 0;
+    for output in &outputs {
+        if output.write() {
+            written += 1;
+            if args.format == Format::Text {
+                println!("Updated {}", output.path.strip_prefix(&paths.root).unwrap_or(&output.path).display());
+            }
+        }
+    }
+
+    remove_orphans(paths, &outputs, &mut report, args.format == Format::Text);
+    cache.save(args, paths);
+
+    let failed_files = report.failed_files();
+    report.success = (failed_files == 0 || !args.fail_on_error) && type_hint_failures == 0;
+    match args.format {
+        Format::Text if written == 0 && report.removed.is_empty() => println!("Declarations are up to date"),
+        Format::Text => {}
+        Format::Json => print_json(&report),
+    }
+    if type_hint_failures > 0 && args.format == Format::Text {
+        eprintln!("Error: invalid $SD-type-hint found with --strict-type-hints");
+    } else if failed_files > 0 && args.format == Format::Text {
+        print_failed_files(args, failed_files);
+    }
+}
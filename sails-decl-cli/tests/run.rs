@@ -0,0 +1,144 @@
+//! Runs the `run` command against projects on disk.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn project(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("sails-decl-run-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("api/models")).unwrap();
+    std::fs::write(root.join(".sailsrc"), "{}").unwrap();
+    root
+}
+
+fn write(root: &Path, path: &str, contents: &str) {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+fn run(root: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sails-decl-rs"))
+        .arg("run")
+        .arg(root)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn failed_models_only_fail_the_run_with_fail_on_error() {
+    let root = project("fail-on-error");
+    write(&root, "api/models/Pet.js", "module.exports = { attributes: { name: { type: 'string' } } };");
+    write(&root, "api/models/Broken.js", "module.exports = 5;");
+
+    let output = run(&root, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(root.join("typings/models/Pet.d.ts").is_file());
+
+    let output = run(&root, &["--fail-on-error"]);
+    assert!(!output.status.success());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn fragments_required_by_models_are_skipped() {
+    let root = project("fragments");
+    write(
+        &root,
+        "api/models/shared/timestamps.js",
+        "module.exports = { createdAt: { type: 'number' } };",
+    );
+    write(
+        &root,
+        "api/models/Pet.js",
+        "const timestamps = require('./shared/timestamps');
+module.exports = { attributes: { ...timestamps, name: { type: 'string' } } };",
+    );
+
+    let output = run(&root, &["--fail-on-error", "--format", "json"]);
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", report);
+    assert!(report.contains("only required by other models"), "{}", report);
+    assert!(!root.join("typings/models/shared/timestamps.d.ts").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+fn declaration(root: &Path, model: &str) -> String {
+    std::fs::read_to_string(root.join("typings/models").join(model).with_extension("d.ts")).unwrap()
+}
+
+#[test]
+fn cached_models_are_redeclared_when_a_required_file_changes() {
+    let root = project("cache-dependencies");
+    write(&root, "api/models/shared/base.js", "module.exports = { name: { type: 'string' } };");
+    write(
+        &root,
+        "api/models/Pet.js",
+        "module.exports = {
+  attributes: { ...require('./shared/base'), ...require('./shared/extra') },
+};",
+    );
+
+    assert!(run(&root, &[]).status.success());
+    assert!(declaration(&root, "Pet").contains(r#"["name"]?: string;"#));
+
+    let output = run(&root, &["--format", "json"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#""cached": true"#));
+
+    write(&root, "api/models/shared/base.js", "module.exports = { name: { type: 'number' } };");
+    assert!(run(&root, &[]).status.success());
+    assert!(declaration(&root, "Pet").contains(r#"["name"]?: number;"#));
+
+    // A require that failed is retried once the file exists
+    write(&root, "api/models/shared/extra.js", "module.exports = { age: { type: 'number' } };");
+    assert!(run(&root, &[]).status.success());
+    assert!(declaration(&root, "Pet").contains(r#"["age"]?: number;"#));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn hook_models_and_helpers_are_declared_unless_the_app_overrides_them() {
+    let root = project("hooks");
+    write(&root, "package.json", r#"{ "dependencies": { "lodash": "*", "sails-hook-pets": "*" } }"#);
+    write(&root, "node_modules/lodash/package.json", "{}");
+    write(&root, "node_modules/sails-hook-pets/package.json", r#"{ "sails": { "isHook": true } }"#);
+    write(
+        &root,
+        "node_modules/sails-hook-pets/api/models/Pet.js",
+        "module.exports = { attributes: { species: { type: 'string' } } };",
+    );
+    write(
+        &root,
+        "node_modules/sails-hook-pets/api/models/Toy.js",
+        "module.exports = { attributes: { squeaks: { type: 'boolean' } } };",
+    );
+    write(
+        &root,
+        "node_modules/sails-hook-pets/api/helpers/feed.js",
+        "module.exports = { fn: async function () {} };",
+    );
+    write(&root, "api/models/Pet.js", "module.exports = { attributes: { name: { type: 'string' } } };");
+
+    let output = run(&root, &["--format", "json"]);
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", report);
+    assert!(report.contains("overridden by the app or an earlier hook"), "{}", report);
+
+    let pet = declaration(&root, "Pet");
+    assert!(pet.contains(r#"["name"]"#) && !pet.contains(r#"["species"]"#), "{}", pet);
+    assert!(declaration(&root, "Toy").contains(r#"["squeaks"]?: boolean;"#));
+    let global = std::fs::read_to_string(root.join("typings/global.d.ts")).unwrap();
+    assert!(global.contains("Toy"), "{}", global);
+    let helpers = std::fs::read_to_string(root.join("typings/helpers.d.ts")).unwrap();
+    assert!(helpers.contains(r#"["feed"]"#), "{}", helpers);
+
+    // Without hooks, the hook's model is an orphan and goes away
+    assert!(run(&root, &["--no-hooks"]).status.success());
+    assert!(!root.join("typings/models/Toy.d.ts").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,66 @@
+//! Runs the `watch` command against a project on disk.
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Kills the watcher when the test ends, even if it fails.
+struct Watcher(Child);
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn project(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("sails-decl-watch-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("api/models")).unwrap();
+    std::fs::write(root.join(".sailsrc"), "{}").unwrap();
+    root
+}
+
+fn write_model(root: &Path, attribute_type: &str) {
+    let code = format!("module.exports = {{ attributes: {{ name: {{ type: '{}' }} }} }};", attribute_type);
+    std::fs::write(root.join("api/models/Pet.js"), code).unwrap();
+}
+
+/// Waits for the declaration of `Pet` to contain `expected`.
+fn wait_for_declaration(root: &Path, expected: &str) -> bool {
+    let declaration = root.join("typings/models/Pet.d.ts");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if std::fs::read_to_string(&declaration).is_ok_and(|code| code.contains(expected)) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+#[test]
+fn watch_from_a_relative_root_picks_up_changes() {
+    let root = project("relative");
+    write_model(&root, "string");
+
+    let _watcher = Watcher(
+        Command::new(env!("CARGO_BIN_EXE_sails-decl-rs"))
+            .args(["watch", "."])
+            .current_dir(&root)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    assert!(wait_for_declaration(&root, r#"["name"]?: string;"#));
+    // Let the watcher subscribe after the first generation
+    std::thread::sleep(Duration::from_millis(500));
+
+    write_model(&root, "number");
+    assert!(wait_for_declaration(&root, r#"["name"]?: number;"#));
+
+    drop(_watcher);
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use swc_common::sync::Lrc;
+use swc_common::{FileName, SourceMap};
+use swc_ecmascript::ast::{Ident, ObjectLit, Script, TsType};
+
+use crate::diagnostics::{Diagnostic, DiagnosticCollector};
+use crate::references::TypeImport;
+use crate::source::parse_source_file;
+use crate::util::{
+    find_module_exports_member, get_bool_prop, get_object_prop, get_prop_as_str, get_str_prop, parse_type_hint,
+};
+
+/// The adapter Sails falls back to when no datastore configures one.
+pub const DEFAULT_ADAPTER: &str = "sails-disk";
+
+/// Which attribute vocabulary model definitions are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SailsVersion {
+    #[default]
+    V1,
+    /// Sails 0.12 / Waterline 0.11 (`text`, `integer`, `datetime`, `enum`, ...).
+    Legacy,
+}
+
+/// Whether attribute types include `null`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Nullability {
+    /// Decided by each attribute's `allowNull`.
+    #[default]
+    Attribute,
+    /// Every attribute may be `null`.
+    Always,
+    /// No attribute is ever `null`.
+    Never,
+}
+
+/// How attribute `type`s are turned into TypeScript types.
+#[derive(Clone, Default)]
+pub struct TypeMapping {
+    /// Replaces the type written for a Sails attribute type, e.g. `json`.
+    pub overrides: HashMap<String, TsType>,
+    pub nullability: Nullability,
+}
+
+impl TypeMapping {
+    /// Parses `ts_type` and uses it for attributes of `sails_type`.
+    pub fn add_override(&mut self, sails_type: &str, ts_type: &str) -> Result<(), String> {
+        let parsed = parse_type_hint(ts_type).map_err(|error| error.message)?;
+        self.overrides.insert(sails_type.to_string(), parsed);
+        Ok(())
+    }
+}
+
+/// Names of the types declared for each model, where `{name}` stands for the
+/// model name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Naming {
+    pub model: String,
+    pub primary_key: String,
+    pub json: String,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Naming {
+            model: "{name}__ModelDecl".to_string(),
+            primary_key: "{name}__PrimaryKey".to_string(),
+            json: "{name}__JSON".to_string(),
+        }
+    }
+}
+
+impl Naming {
+    pub fn model_type(&self, model_name: &str) -> String {
+        self.model.replace("{name}", model_name)
+    }
+
+    pub fn primary_key_type(&self, model_name: &str) -> String {
+        self.primary_key.replace("{name}", model_name)
+    }
+
+    pub fn json_type(&self, model_name: &str) -> String {
+        self.json.replace("{name}", model_name)
+    }
+
+    /// Checks that every template contains `{name}` and makes a valid
+    /// TypeScript identifier out of a valid model name.
+    pub fn validate(&self) -> Result<(), String> {
+        for template in [&self.model, &self.primary_key, &self.json] {
+            if !template.contains("{name}") {
+                return Err(format!("'{}' must contain {{name}}", template));
+            }
+            if Ident::verify_symbol(&template.replace("{name}", "Model")).is_err() {
+                return Err(format!("'{}' does not make a valid TypeScript identifier", template));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Model-level settings that influence the generated declarations. The same
+/// shape is read from a model definition and from `config/models.js`, where it
+/// provides the defaults every model inherits.
+#[derive(Clone, Default)]
+pub struct ModelSettings {
+    pub primary_key: Option<String>,
+    pub datastore: Option<String>,
+    pub dont_use_object_ids: Option<bool>,
+    pub schema: Option<bool>,
+    /// Sails 0.12 `autoPK`, `autoCreatedAt` and `autoUpdatedAt`.
+    pub auto_pk: Option<bool>,
+    pub auto_created_at: Option<bool>,
+    pub auto_updated_at: Option<bool>,
+    pub attributes: Vec<(String, ObjectLit)>,
+}
+
+impl ModelSettings {
+    pub fn from_object(obj: &ObjectLit) -> Self {
+        let attributes = get_object_prop(obj, "attributes")
+            .map(|attributes| {
+                attributes
+                    .props
+                    .iter()
+                    .filter_map(|prop| {
+                        let key_value = prop.as_prop()?.as_key_value()?;
+                        let name = get_prop_as_str(&key_value.key)?;
+                        Some((name.to_string(), key_value.value.as_object()?.clone()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ModelSettings {
+            primary_key: get_str_prop(obj, "primaryKey").map(str::to_string),
+            datastore: get_str_prop(obj, "datastore").map(str::to_string),
+            dont_use_object_ids: get_bool_prop(obj, "dontUseObjectIds"),
+            schema: get_bool_prop(obj, "schema"),
+            auto_pk: get_bool_prop(obj, "autoPK"),
+            auto_created_at: get_bool_prop(obj, "autoCreatedAt"),
+            auto_updated_at: get_bool_prop(obj, "autoUpdatedAt"),
+            attributes,
+        }
+    }
+}
+
+/// Project-wide settings read from the Sails `config/` directory.
+#[derive(Clone, Default)]
+pub struct ProjectConfig {
+    pub sails_version: SailsVersion,
+    /// Treat an invalid `$SD-type-hint` as an error for the whole model or
+    /// helper instead of skipping just that attribute.
+    pub strict_type_hints: bool,
+    /// Project types that hints may refer to by name.
+    pub type_imports: Vec<TypeImport>,
+    pub types: TypeMapping,
+    pub naming: Naming,
+    pub models: ModelSettings,
+    /// Datastore name to adapter package name, from `config/datastores.js`.
+    pub datastores: HashMap<String, String>,
+    /// Problems found while reading the config files.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ProjectConfig {
+    /// Reads `config/models.js` and `config/datastores.js` under `project_root`.
+    /// Missing or unparsable files leave the corresponding settings at their defaults.
+    pub fn load(project_root: &Path) -> Self {
+        let [models_file, datastores_file] = Self::files(project_root);
+        let mut diagnostics = vec![];
+
+        let models = parse_config_file(&models_file, &mut diagnostics)
+            .and_then(|script| find_module_exports_member(script, "models"))
+            .map(|obj| ModelSettings::from_object(&obj))
+            .unwrap_or_default();
+
+        let datastores = parse_config_file(&datastores_file, &mut diagnostics)
+            .and_then(|script| find_module_exports_member(script, "datastores"))
+            .map(|obj| {
+                obj.props
+                    .iter()
+                    .filter_map(|prop| {
+                        let key_value = prop.as_prop()?.as_key_value()?;
+                        let name = get_prop_as_str(&key_value.key)?;
+                        let adapter = get_str_prop(key_value.value.as_object()?, "adapter")?;
+                        Some((name.to_string(), adapter.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ProjectConfig {
+            sails_version: SailsVersion::default(),
+            strict_type_hints: false,
+            type_imports: vec![],
+            types: TypeMapping::default(),
+            naming: Naming::default(),
+            models,
+            datastores,
+            diagnostics,
+        }
+    }
+
+    /// The files [`ProjectConfig::load`] reads.
+    pub fn files(project_root: &Path) -> [PathBuf; 2] {
+        let config_dir = project_root.join("config");
+        [config_dir.join("models.js"), config_dir.join("datastores.js")]
+    }
+
+    pub fn adapter_for(&self, datastore: Option<&str>) -> &str {
+        self.datastores
+            .get(datastore.unwrap_or("default"))
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_ADAPTER)
+    }
+}
+
+/// Where Sails looks for models and helpers, from the `paths` setting.
+/// Relative paths are relative to the project root.
+#[derive(Clone, Debug, Default)]
+pub struct SailsPaths {
+    pub models: Option<String>,
+    pub helpers: Option<String>,
+    /// Problems found while reading the config files.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl SailsPaths {
+    /// Reads `paths` from `config/paths.js`, then from the file in
+    /// `config/env/` for `NODE_ENV` (`development` when unset), then from
+    /// `config/local.js`, each overriding the last as in Sails.
+    pub fn load(project_root: &Path) -> Self {
+        let config_dir = project_root.join("config");
+        let environment = std::env::var("NODE_ENV").unwrap_or_else(|_| "development".to_string());
+        let mut paths = SailsPaths::default();
+        for file in [
+            config_dir.join("paths.js"),
+            config_dir.join("env").join(format!("{}.js", environment)),
+            config_dir.join("local.js"),
+        ] {
+            let Some(obj) = parse_config_file(&file, &mut paths.diagnostics)
+                .and_then(|script| find_module_exports_member(script, "paths"))
+            else {
+                continue;
+            };
+            if let Some(models) = get_str_prop(&obj, "models") {
+                paths.models = Some(models.to_string());
+            }
+            if let Some(helpers) = get_str_prop(&obj, "helpers") {
+                paths.helpers = Some(helpers.to_string());
+            }
+        }
+        paths
+    }
+}
+
+fn parse_config_file(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Option<Script> {
+    let code = std::fs::read_to_string(path).ok()?;
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let collector = DiagnosticCollector::new(cm.clone(), Some(path.to_path_buf()));
+
+    let file = cm.new_source_file(FileName::Real(path.to_path_buf()).into(), code);
+
+    let script = parse_source_file(&file, None, collector.handler());
+    diagnostics.extend(collector.diagnostics());
+    script
+}
@@ -0,0 +1,278 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use swc_common::errors::{DiagnosticBuilder, DiagnosticId, Emitter, Handler, Level};
+use swc_common::sync::Lrc;
+use swc_common::{FileName, SourceMap, Span};
+
+/// Stable identifiers for the kinds of diagnostics this crate reports.
+pub mod codes {
+    /// The file is not valid JavaScript or TypeScript.
+    pub const SYNTAX: &str = "syntax";
+    /// A `$SD-type-hint` or JSDoc `@type` is not a valid TypeScript type.
+    pub const INVALID_TYPE_HINT: &str = "invalid-type-hint";
+    /// The file could not be read.
+    pub const UNREADABLE_FILE: &str = "unreadable-file";
+    /// Nothing is assigned to `module.exports`.
+    pub const NO_EXPORTS: &str = "no-exports";
+    /// `module.exports` is not an object, or not one that could be resolved.
+    pub const EXPORTS_NOT_OBJECT: &str = "exports-not-object";
+    /// A model without an `attributes` object.
+    pub const MISSING_ATTRIBUTES: &str = "missing-attributes";
+    /// A helper without an `fn` function.
+    pub const MISSING_HELPER_FN: &str = "missing-helper-fn";
+    /// A `model:` association names a model that was not found.
+    pub const UNKNOWN_MODEL: &str = "unknown-model";
+
+    pub(crate) const ALL: &[&str] = &[
+        SYNTAX,
+        INVALID_TYPE_HINT,
+        UNREADABLE_FILE,
+        NO_EXPORTS,
+        EXPORTS_NOT_OBJECT,
+        MISSING_ATTRIBUTES,
+        MISSING_HELPER_FN,
+        UNKNOWN_MODEL,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// A position in a source file. Lines and columns start at 1, and columns
+/// count characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceSpan {
+    pub start: Location,
+    pub end: Location,
+}
+
+/// A problem found while reading a model or helper.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// One of [`codes`].
+    pub code: &'static str,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub span: Option<SourceSpan>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, file: Option<PathBuf>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            file,
+            span: None,
+            notes: vec![],
+        }
+    }
+}
+
+/// Maps the stored code back to the matching constant in [`codes`].
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Diagnostic {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Stored {
+            severity: Severity,
+            code: String,
+            message: String,
+            file: Option<PathBuf>,
+            span: Option<SourceSpan>,
+            notes: Vec<String>,
+        }
+
+        let stored = Stored::deserialize(deserializer)?;
+        let code = codes::ALL
+            .iter()
+            .find(|known| **known == stored.code)
+            .copied()
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown diagnostic code `{}`", stored.code)))?;
+        Ok(Diagnostic {
+            severity: stored.severity,
+            code,
+            message: stored.message,
+            file: stored.file,
+            span: stored.span,
+            notes: stored.notes,
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(file) = &self.file {
+            write!(f, "\n  --> {}", file.display())?;
+            if let Some(span) = &self.span {
+                write!(f, ":{}:{}", span.start.line, span.start.column)?;
+            }
+        }
+        for note in &self.notes {
+            write!(f, "\n  = note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error along with every diagnostic reported before it happened.
+#[derive(Debug)]
+pub struct Diagnosed<E> {
+    pub error: E,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<E: fmt::Display> fmt::Display for Diagnosed<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Diagnosed<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Collects the diagnostics emitted to a `Handler` instead of printing them.
+pub(crate) struct DiagnosticCollector {
+    cm: Lrc<SourceMap>,
+    /// The file being processed, for diagnostics without a position.
+    file: Option<PathBuf>,
+    handler: Handler,
+    emitted: Arc<Mutex<Vec<swc_common::errors::Diagnostic>>>,
+}
+
+struct CollectingEmitter(Arc<Mutex<Vec<swc_common::errors::Diagnostic>>>);
+
+impl Emitter for CollectingEmitter {
+    fn emit(&mut self, db: &mut DiagnosticBuilder<'_>) {
+        if let Ok(mut emitted) = self.0.lock() {
+            emitted.push((**db).clone());
+        }
+    }
+}
+
+impl DiagnosticCollector {
+    pub fn new(cm: Lrc<SourceMap>, file: Option<PathBuf>) -> Self {
+        let emitted = Arc::new(Mutex::new(vec![]));
+        let handler = Handler::with_emitter(true, false, Box::new(CollectingEmitter(emitted.clone())));
+        DiagnosticCollector {
+            cm,
+            file,
+            handler,
+            emitted,
+        }
+    }
+
+    pub fn handler(&self) -> &Handler {
+        &self.handler
+    }
+
+    /// Converts everything emitted so far, resolving spans against the source map.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let emitted = match self.emitted.lock() {
+            Ok(emitted) => emitted.clone(),
+            Err(_) => return vec![],
+        };
+        emitted.iter().map(|diagnostic| self.convert(diagnostic)).collect()
+    }
+
+    /// Wraps `error` with the diagnostics collected so far, plus one describing
+    /// the error itself when nothing more specific was reported.
+    pub fn fail<E: fmt::Display>(&self, error: E, code: &'static str) -> Diagnosed<E> {
+        let mut diagnostics = self.diagnostics();
+        if !diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+            diagnostics.push(Diagnostic::error(code, error.to_string(), self.file.clone()));
+        }
+        Diagnosed { error, diagnostics }
+    }
+
+    fn convert(&self, diagnostic: &swc_common::errors::Diagnostic) -> Diagnostic {
+        let severity = match diagnostic.level {
+            Level::Warning => Severity::Warning,
+            Level::Note | Level::Help => Severity::Note,
+            _ => Severity::Error,
+        };
+        let code = match &diagnostic.code {
+            Some(DiagnosticId::Error(code) | DiagnosticId::Lint(code)) => {
+                codes::ALL.iter().find(|known| **known == code).copied().unwrap_or(codes::SYNTAX)
+            }
+            None => codes::SYNTAX,
+        };
+        let message = diagnostic.message.iter().map(|message| message.0.as_str()).collect();
+
+        let (file, span) = match diagnostic.span.primary_span().filter(|span| !span.is_dummy()) {
+            Some(span) => self.locate(span),
+            None => (self.file.clone(), None),
+        };
+
+        let notes = diagnostic
+            .children
+            .iter()
+            .map(|child| child.message.iter().map(|message| message.0.as_str()).collect())
+            .collect();
+
+        Diagnostic {
+            severity,
+            code,
+            message,
+            file,
+            span,
+            notes,
+        }
+    }
+
+    fn locate(&self, span: Span) -> (Option<PathBuf>, Option<SourceSpan>) {
+        let start = self.cm.lookup_char_pos(span.lo);
+        let end = self.cm.lookup_char_pos(span.hi);
+        let file = match &*start.file.name {
+            FileName::Real(path) => Some(path.clone()),
+            _ => None,
+        };
+        let location = |loc: &swc_common::Loc| Location {
+            line: loc.line,
+            column: loc.col.0 + 1,
+        };
+        (
+            file,
+            Some(SourceSpan {
+                start: location(&start),
+                end: location(&end),
+            }),
+        )
+    }
+}
@@ -0,0 +1,298 @@
+use swc_common::comments::{Comment, CommentKind, Comments, SingleThreadedComments};
+use swc_common::{BytePos, DUMMY_SP, GLOBALS, Globals, Span};
+use swc_ecma_codegen::Result;
+use swc_ecma_codegen::text_writer::WriteJs;
+use swc_ecmascript::ast::{Expr, Lit, ObjectLit, UnaryOp};
+
+use crate::util::get_prop_as_str;
+
+/// Validation rules Sails accepts on attributes and inputs, rendered as JSDoc tags.
+const VALIDATION_RULES: &[&str] = &[
+    "isAfter",
+    "isBefore",
+    "isBoolean",
+    "isCreditCard",
+    "isEmail",
+    "isHexColor",
+    "isIn",
+    "isInteger",
+    "isIP",
+    "isNotEmptyString",
+    "isNotIn",
+    "isNumber",
+    "isString",
+    "isURL",
+    "isUUID",
+    "max",
+    "maxLength",
+    "min",
+    "minLength",
+    "regex",
+];
+
+/// Renders simple literals (and arrays of them) the way they were written.
+fn literal_source(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(Lit::Str(string)) => Some(format!("{:?}", string.value.as_str()?)),
+        Expr::Lit(Lit::Num(number)) => Some(number.value.to_string()),
+        Expr::Lit(Lit::Bool(boolean)) => Some(boolean.value.to_string()),
+        Expr::Lit(Lit::Null(_)) => Some("null".to_string()),
+        Expr::Lit(Lit::Regex(regex)) => Some(format!("/{}/{}", regex.exp, regex.flags)),
+        Expr::Unary(unary) if unary.op == UnaryOp::Minus => {
+            Some(format!("-{}", literal_source(&unary.arg)?))
+        }
+        Expr::Array(array) => {
+            let elems = array
+                .elems
+                .iter()
+                .map(|elem| literal_source(&elem.as_ref()?.expr))
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("[{}]", elems.join(", ")))
+        }
+        _ => None,
+    }
+}
+
+fn text_lines(expr: &Expr) -> Vec<String> {
+    match expr.as_lit().and_then(|lit| lit.as_str()).and_then(|s| s.value.as_str()) {
+        Some(text) => text.lines().map(|line| line.trim_end().to_string()).collect(),
+        None => vec![],
+    }
+}
+
+/// Builds the JSDoc lines for an attribute or helper input definition from its
+/// `description`, `extendedDescription`, `example`, `moreInfoUrl`, `defaultsTo`
+/// and validation rules.
+pub fn attribute_doc(attribute: &ObjectLit) -> Vec<String> {
+    let mut description = vec![];
+    let mut extended = vec![];
+    let mut tags = vec![];
+    let mut rules = vec![];
+
+    for prop in &attribute.props {
+        let Some(key_value) = prop.as_prop().and_then(|p| p.as_key_value()) else {
+            continue;
+        };
+        let Some(name) = get_prop_as_str(&key_value.key) else {
+            continue;
+        };
+
+        match name {
+            "description" => description = text_lines(&key_value.value),
+            "extendedDescription" => extended = text_lines(&key_value.value),
+            "example" => {
+                if let Some(example) = literal_source(&key_value.value) {
+                    tags.push(format!("@example {}", example));
+                }
+            }
+            "moreInfoUrl" => {
+                if let Some(url) = text_lines(&key_value.value).first() {
+                    tags.push(format!("@see {}", url));
+                }
+            }
+            "defaultsTo" => {
+                if let Some(default) = literal_source(&key_value.value) {
+                    tags.push(format!("@default {}", default));
+                }
+            }
+            rule if VALIDATION_RULES.contains(&rule) => match literal_source(&key_value.value).as_deref() {
+                Some("true") => rules.push(format!("@{}", rule)),
+                Some("false") | None => {}
+                Some(value) => rules.push(format!("@{} {}", rule, value)),
+            },
+            _ => {}
+        }
+    }
+
+    join_sections(description, extended, [tags, rules].concat())
+}
+
+/// Builds the JSDoc lines for a helper from its `description`,
+/// `extendedDescription` and `moreInfoUrl`.
+pub fn helper_doc(definition: &ObjectLit) -> Vec<String> {
+    let mut description = vec![];
+    let mut extended = vec![];
+    let mut tags = vec![];
+
+    for prop in &definition.props {
+        let Some(key_value) = prop.as_prop().and_then(|p| p.as_key_value()) else {
+            continue;
+        };
+        match get_prop_as_str(&key_value.key) {
+            Some("description") => description = text_lines(&key_value.value),
+            Some("extendedDescription") => extended = text_lines(&key_value.value),
+            Some("moreInfoUrl") => {
+                if let Some(url) = text_lines(&key_value.value).first() {
+                    tags.push(format!("@see {}", url));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    join_sections(description, extended, tags)
+}
+
+fn join_sections(description: Vec<String>, extended: Vec<String>, tags: Vec<String>) -> Vec<String> {
+    let mut lines = description;
+    if !extended.is_empty() {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(extended);
+    }
+    lines.extend(tags);
+    lines
+}
+
+/// Leading JSDoc comments for generated nodes. Each block is attached to the
+/// position of the node it documents, or to a reserved span that the emitter
+/// prints comments for but the source map ignores when the node has none.
+#[derive(Default)]
+pub struct DocComments {
+    comments: SingleThreadedComments,
+    globals: Globals,
+}
+
+impl DocComments {
+    /// Attaches `lines` as a JSDoc block to a node at `span`, nested `depth`
+    /// levels deep, and returns the span to give the node.
+    pub fn attach(&self, lines: &[String], depth: usize, span: Span) -> Span {
+        if lines.is_empty() {
+            return span;
+        }
+
+        // The emitter writes `/*`, the text and `*/`, then `DocWriter` breaks the line
+        let text = if lines.len() == 1 {
+            format!("* {} ", escape(&lines[0]))
+        } else {
+            let indent = "    ".repeat(depth);
+            let mut text = "*\n".to_string();
+            for line in lines {
+                if line.is_empty() {
+                    text.push_str(&format!("{} *\n", indent));
+                } else {
+                    text.push_str(&format!("{} * {}\n", indent, escape(line)));
+                }
+            }
+            text.push_str(&format!("{} ", indent));
+            text
+        };
+
+        let span = match span.is_dummy() {
+            true => GLOBALS.set(&self.globals, Span::dummy_with_cmt),
+            false => span,
+        };
+        self.comments.add_leading(
+            span.lo,
+            Comment {
+                kind: CommentKind::Block,
+                span: DUMMY_SP,
+                text: text.into(),
+            },
+        );
+        span
+    }
+
+    pub fn comments(&self) -> &SingleThreadedComments {
+        &self.comments
+    }
+}
+
+/// Puts the node after a JSDoc block on its own line, where the emitter
+/// would continue on the line of the `*/`.
+pub struct DocWriter<W> {
+    inner: W,
+    after_comment: bool,
+}
+
+impl<W: WriteJs> DocWriter<W> {
+    pub fn new(inner: W) -> Self {
+        DocWriter {
+            inner,
+            after_comment: false,
+        }
+    }
+}
+
+impl<W: WriteJs> WriteJs for DocWriter<W> {
+    fn increase_indent(&mut self) -> Result {
+        self.inner.increase_indent()
+    }
+
+    fn decrease_indent(&mut self) -> Result {
+        self.inner.decrease_indent()
+    }
+
+    fn write_semi(&mut self, span: Option<Span>) -> Result {
+        self.inner.write_semi(span)
+    }
+
+    fn write_space(&mut self) -> Result {
+        match std::mem::take(&mut self.after_comment) {
+            true => self.inner.write_line(),
+            false => self.inner.write_space(),
+        }
+    }
+
+    fn write_keyword(&mut self, span: Option<Span>, s: &'static str) -> Result {
+        self.inner.write_keyword(span, s)
+    }
+
+    fn write_operator(&mut self, span: Option<Span>, s: &str) -> Result {
+        self.inner.write_operator(span, s)
+    }
+
+    fn write_param(&mut self, s: &str) -> Result {
+        self.inner.write_param(s)
+    }
+
+    fn write_property(&mut self, s: &str) -> Result {
+        self.inner.write_property(s)
+    }
+
+    fn write_line(&mut self) -> Result {
+        self.inner.write_line()
+    }
+
+    fn write_lit(&mut self, span: Span, s: &str) -> Result {
+        self.inner.write_lit(span, s)
+    }
+
+    fn write_comment(&mut self, s: &str) -> Result {
+        self.after_comment = s == "*/";
+        self.inner.write_comment(s)
+    }
+
+    fn write_str_lit(&mut self, span: Span, s: &str) -> Result {
+        self.inner.write_str_lit(span, s)
+    }
+
+    fn write_str(&mut self, s: &str) -> Result {
+        self.inner.write_str(s)
+    }
+
+    fn write_symbol(&mut self, span: Span, s: &str) -> Result {
+        self.inner.write_symbol(span, s)
+    }
+
+    fn write_punct(&mut self, span: Option<Span>, s: &'static str, commit_pending_semi: bool) -> Result {
+        self.inner.write_punct(span, s, commit_pending_semi)
+    }
+
+    fn care_about_srcmap(&self) -> bool {
+        self.inner.care_about_srcmap()
+    }
+
+    fn add_srcmap(&mut self, pos: BytePos) -> Result {
+        self.inner.add_srcmap(pos)
+    }
+
+    fn commit_pending_semi(&mut self) -> Result {
+        self.inner.commit_pending_semi()
+    }
+}
+
+fn escape(line: &str) -> String {
+    line.replace("*/", "*\\/")
+}
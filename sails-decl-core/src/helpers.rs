@@ -1,78 +1,113 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
-use swc_common::Spanned;
-use swc_common::source_map::DefaultSourceMapGenConfig;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use swc_common::{Span, Spanned};
+use swc_common::comments::SingleThreadedComments;
 use swc_common::sync::Lrc;
 
 use swc_common::{
     FileName, SourceMap,
-    errors::{ColorConfig, Handler},
 };
 use swc_ecma_codegen::text_writer::JsWriter;
 use swc_ecma_codegen::{Config, Emitter};
-use swc_ecma_parser::{Lexer, Parser, StringInput, Syntax};
 use swc_ecmascript::ast::{
     Decl, ExportDecl, Expr, Ident, Lit, Module, ModuleItem, Str, TsEntityName, TsKeywordType, TsPropertySignature, TsType, TsTypeAliasDecl, TsTypeAnn, TsTypeElement, TsTypeLit, TsTypeParamInstantiation, TsTypeRef
 };
 
+use crate::config::{ProjectConfig, SailsVersion};
+use crate::docs::{DocComments, DocWriter, attribute_doc, helper_doc};
+use crate::model::ModelIndex;
+use crate::references::resolve_type_references;
 use crate::literal_declarations::{
     get_global_declarations, get_global_model_accessors, get_global_namespace_declarations,
     get_helper_object_interface, get_model_accessor_interface, get_sails_object,
     get_sails_object_models_interface, import_named, SailsModelInfo,
 };
-use crate::util::{EmittedCode, find_module_exports, get_prop_as_str, ts_type_from_attribute};
+use crate::diagnostics::{Diagnosed, Diagnostic, DiagnosticCollector, codes};
+use crate::resolver::{ModuleResolver, Scope};
+use crate::source::{parse_source_file, strip_extension};
+use crate::util::{
+    EmittedCode, RelativeSources, emit_type_hint_error, find_module_exports_expr, get_prop_as_str, ts_type_from_attribute,
+    with_jsdoc_type_hint,
+};
 
 pub fn build_tree(
     helpers: &[PathBuf],
-    helpers_folder: &PathBuf,
+    helpers_folders: &[PathBuf],
     cm: Lrc<SourceMap>,
+    project: &ProjectConfig,
+    failures: &mut Vec<(PathBuf, Diagnosed<GenHelperDeclError>)>,
+) -> Vec<SailsDeclHelperTreeNode> {
+    let mut load = |path: PathBuf| get_helper_info(path, cm.clone(), project);
+    build_tree_with(helpers, helpers_folders, &mut load, failures)
+}
+
+/// Like [`build_tree`], but gets each helper's info from `load`.
+fn build_tree_with(
+    helpers: &[PathBuf],
+    helpers_folders: &[PathBuf],
+    load: &mut HelperLoader<'_>,
+    failures: &mut Vec<(PathBuf, Diagnosed<GenHelperDeclError>)>,
 ) -> Vec<SailsDeclHelperTreeNode> {
-    // Initial pass: make sure we are only looking at paths relative to the root folder
-    let relative_paths: Vec<PathBuf> = helpers
+    // Initial pass: make sure we are only looking at paths relative to the root folders,
+    // ordered by folder so that the helpers of earlier ones win
+    let mut relative_paths: Vec<(usize, PathBuf, PathBuf)> = helpers
         .iter()
-        .filter_map(|p| p.strip_prefix(&helpers_folder).ok().map(|s| s.to_path_buf()))
+        .filter_map(|p| {
+            helpers_folders
+                .iter()
+                .enumerate()
+                .find_map(|(i, folder)| Some((i, p.strip_prefix(folder).ok()?.to_path_buf(), p.clone())))
+        })
+        .collect();
+    relative_paths.sort_by_key(|(i, _, _)| *i);
+    let relative_paths: Vec<(PathBuf, PathBuf)> = relative_paths
+        .into_iter()
+        .map(|(_, relative, full)| (relative, full))
         .collect();
 
-    build_tree_recursive(&relative_paths, &helpers_folder, cm)
+    build_tree_recursive(&relative_paths, load, failures)
 }
 
+type HelperLoader<'a> = dyn FnMut(PathBuf) -> Result<SailsHelperInfo, Diagnosed<GenHelperDeclError>> + 'a;
+
+// `paths` pairs each helper's path relative to the current level with its full path
 fn build_tree_recursive(
-    paths: &[PathBuf],
-    current_base: &PathBuf,
-    cm: Lrc<SourceMap>,
+    paths: &[(PathBuf, PathBuf)],
+    load: &mut HelperLoader<'_>,
+    failures: &mut Vec<(PathBuf, Diagnosed<GenHelperDeclError>)>,
 ) -> Vec<SailsDeclHelperTreeNode> {
     let mut nodes = Vec::new();
-    let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    // Sorted, so the members of `HelpersObject` come out in the same order every run
+    let mut groups: BTreeMap<String, Vec<(PathBuf, PathBuf)>> = BTreeMap::new();
 
     // Group paths by their next immediate component
-    for path in paths {
-        if let Some(first) = path.components().next() {
+    for (relative, full) in paths {
+        if let Some(first) = relative.components().next() {
             let name = first.as_os_str().to_string_lossy().into_owned();
-            groups.entry(name).or_default().push(path.clone());
+            groups.entry(name).or_default().push((relative.clone(), full.clone()));
         }
     }
 
     for (name, paths_in_group) in groups {
-        let full_path = current_base.join(&name);
-
         // Check if this group represents a single leaf node (the file itself)
-        // This happens when one of the paths in the group is exactly the 'name'
-        if paths_in_group.iter().any(|p| p.components().count() == 1) {
-            if full_path.extension().and_then(|s| s.to_str()) == Some("js") {
-                match get_helper_info(full_path, cm.clone()) {
-                    Ok(helper_info) => nodes.push(SailsDeclHelperTreeNode::Helper(helper_info)),
-                    Err(e) => eprintln!("Failed to parse helper: {:?}", e),
-                }
+        // This happens when one of the paths in the group is exactly the 'name'.
+        // If several folders have it, the first one's is used.
+        if let Some((_, full_path)) = paths_in_group.iter().find(|(p, _)| p.components().count() == 1) {
+            match load(full_path.clone()) {
+                Ok(helper_info) => nodes.push(SailsDeclHelperTreeNode::Helper(Box::new(helper_info))),
+                Err(e) => failures.push((full_path.clone(), e)),
             }
         } else {
             // Otherwise, it's a directory. Strip the prefix and recurse.
-            let sub_paths: Vec<PathBuf> = paths_in_group
+            let sub_paths: Vec<(PathBuf, PathBuf)> = paths_in_group
                 .into_iter()
-                .filter_map(|p| p.strip_prefix(&name).ok().map(|s| s.to_path_buf()))
+                .filter_map(|(p, full)| p.strip_prefix(&name).ok().map(|s| (s.to_path_buf(), full)))
                 .collect();
 
             if !sub_paths.is_empty() {
-                let children = build_tree_recursive(&sub_paths, &full_path, cm.clone());
+                let children = build_tree_recursive(&sub_paths, load, failures);
                 nodes.push(SailsDeclHelperTreeNode::Directory(SailsDeclHelperDirectory {
                     name: normalize_name(&name),
                     children,
@@ -99,12 +134,7 @@ fn normalize_name(name: &str) -> String {
         }
     }
 
-    // if result ends in .js, remove that
-    if result.ends_with(".js") {
-        result.truncate(result.len() - 3);
-    }
-
-    result
+    strip_extension(&result).to_string()
 }
 
 #[derive(Clone)]
@@ -113,6 +143,14 @@ pub struct SailsHelperInfo {
     pub name: String,
     pub return_type: Option<TsType>,
     pub input_type: Option<TsType>,
+    /// JSDoc lines for the helper itself.
+    pub doc: Vec<String>,
+    /// JSDoc lines for each documented input, by input name.
+    pub input_docs: Vec<(String, Vec<String>)>,
+    /// Problems found in the helper that did not stop it from being declared.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Files read through `require()` while resolving the definition.
+    pub dependencies: Vec<PathBuf>,
 }
 
 #[derive(Debug)]
@@ -121,41 +159,54 @@ pub enum GenHelperDeclError {
     IsNotCommonJsModule,
     IsNotHelper,
     CommonJsModuleDoesNotExportObject,
+    SDTypeHintParseError,
 }
 
-pub fn get_helper_info(helper: PathBuf, cm: Lrc<SourceMap>) -> Result<SailsHelperInfo, GenHelperDeclError> {
-    let code = std::fs::read_to_string(&helper).map_err(|_| GenHelperDeclError::ParseError)?;
-
-    let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
-
-    let file = cm.new_source_file(FileName::Real(helper.clone()).into(), code);
+impl std::fmt::Display for GenHelperDeclError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GenHelperDeclError::ParseError => "failed to parse helper",
+            GenHelperDeclError::IsNotCommonJsModule => "helper does not assign `module.exports`",
+            GenHelperDeclError::IsNotHelper => "helper does not define an `fn` function",
+            GenHelperDeclError::CommonJsModuleDoesNotExportObject => "helper does not export an object",
+            GenHelperDeclError::SDTypeHintParseError => "helper has an invalid `$SD-type-hint`",
+        })
+    }
+}
 
-    let lexer = Lexer::new(
-        Syntax::Es(Default::default()),
-        Default::default(),
-        StringInput::from(&*file),
-        None,
-    );
+impl std::error::Error for GenHelperDeclError {}
 
-    let mut parser = Parser::new_from(lexer);
+pub fn get_helper_info(
+    helper: PathBuf,
+    cm: Lrc<SourceMap>,
+    project: &ProjectConfig,
+) -> Result<SailsHelperInfo, Diagnosed<GenHelperDeclError>> {
+    let collector = DiagnosticCollector::new(cm.clone(), Some(helper.clone()));
+    let handler = collector.handler();
 
-    for e in parser.take_errors() {
-        e.into_diagnostic(&handler).emit();
-    }
+    let code = std::fs::read_to_string(&helper)
+        .map_err(|_| collector.fail(GenHelperDeclError::ParseError, codes::UNREADABLE_FILE))?;
 
-    let module_result = parser.parse_commonjs().map_err(|e| {
-        // Unrecoverable fatal error occurred
-        e.into_diagnostic(&handler).emit()
-    });
+    let file = cm.new_source_file(FileName::Real(helper.clone()).into(), code);
 
-    if module_result.is_err() {
-        return Err(GenHelperDeclError::ParseError);
-    }
+    // Comments are kept so JSDoc `@type` tags can serve as type hints
+    let comments = SingleThreadedComments::default();
 
-    let module = module_result.unwrap();
+    let module = parse_source_file(&file, Some(&comments), handler)
+        .ok_or_else(|| collector.fail(GenHelperDeclError::ParseError, codes::SYNTAX))?;
 
-    let module_exports_obj =
-        find_module_exports(module).ok_or(GenHelperDeclError::CommonJsModuleDoesNotExportObject)?;
+    // Inputs may refer to shared constants or fragments, like model attributes
+    let module_exports = find_module_exports_expr(&module)
+        .ok_or_else(|| collector.fail(GenHelperDeclError::IsNotCommonJsModule, codes::NO_EXPORTS))?;
+    let scope = Scope {
+        script: &module,
+        path: Some(&helper),
+    };
+    let mut resolver = ModuleResolver::new(cm.clone(), &comments, handler);
+    let module_exports_obj = resolver
+        .resolve_object(&module_exports, scope)
+        .ok_or_else(|| collector.fail(GenHelperDeclError::CommonJsModuleDoesNotExportObject, codes::EXPORTS_NOT_OBJECT))?;
+    let dependencies = resolver.dependencies();
 
     let _fn_obj = module_exports_obj
         .props
@@ -170,7 +221,7 @@ pub fn get_helper_info(helper: PathBuf, cm: Lrc<SourceMap>) -> Result<SailsHelpe
 
             _key_value_prop.value.as_fn_expr().cloned()
         })
-        .ok_or(GenHelperDeclError::IsNotHelper)?;
+        .ok_or_else(|| collector.fail(GenHelperDeclError::IsNotHelper, codes::MISSING_HELPER_FN))?;
 
     let inputs_obj = module_exports_obj.props.iter().find_map(|prop| {
         let _key_value_prop = prop.as_prop().and_then(|p| p.as_key_value())?;
@@ -188,15 +239,21 @@ pub fn get_helper_info(helper: PathBuf, cm: Lrc<SourceMap>) -> Result<SailsHelpe
         // we'll just return a default helper declaration with no input type instead of erroring out
         return Ok(SailsHelperInfo {
             path: helper.clone(),
-            name: normalize_name(helper.file_name().unwrap().to_str().unwrap()),
+            name: normalize_name(&helper.file_name().unwrap_or_default().to_string_lossy()),
             return_type: None,
             input_type: None,
+            doc: helper_doc(&module_exports_obj),
+            input_docs: vec![],
+            diagnostics: collector.diagnostics(),
+            dependencies,
         });
     }
 
     let inputs_obj = inputs_obj.unwrap();
 
     let mut inputs: Vec<TsTypeElement> = vec![];
+    let mut input_docs = vec![];
+    let mut type_hint_errors = false;
 
     for input in &inputs_obj.props {
         let input_pair = match input.as_prop().and_then(|p| p.as_key_value()) {
@@ -210,15 +267,25 @@ pub fn get_helper_info(helper: PathBuf, cm: Lrc<SourceMap>) -> Result<SailsHelpe
         };
 
         let _input_value_obj = match input_pair.value.as_object() {
-            Some(obj) => obj,
+            Some(obj) => with_jsdoc_type_hint(Cow::Borrowed(obj), &comments, input_pair.key.span().lo),
             None => continue,
         };
 
-        let input_type_info = match ts_type_from_attribute(_input_value_obj) {
-            Some(info) => info,
-            None => continue,
+        let input_type_info = match ts_type_from_attribute(&_input_value_obj, SailsVersion::V1, &project.types) {
+            Ok(Some(info)) => info,
+            Ok(None) => continue,
+            Err(error) => {
+                emit_type_hint_error(handler, &error, project.strict_type_hints);
+                type_hint_errors = true;
+                continue;
+            }
         };
 
+        let doc = attribute_doc(&_input_value_obj);
+        if !doc.is_empty() {
+            input_docs.push((_input_key_ident.sym.to_string(), doc));
+        }
+
         inputs.push(TsTypeElement::TsPropertySignature(TsPropertySignature {
             span: input_pair.key.span(),
             readonly: false,
@@ -236,15 +303,23 @@ pub fn get_helper_info(helper: PathBuf, cm: Lrc<SourceMap>) -> Result<SailsHelpe
         }));
     }
 
-    return Ok(SailsHelperInfo {
+    if type_hint_errors && project.strict_type_hints {
+        return Err(collector.fail(GenHelperDeclError::SDTypeHintParseError, codes::INVALID_TYPE_HINT));
+    }
+
+    Ok(SailsHelperInfo {
         path: helper.clone(),
-        name: normalize_name(helper.file_name().unwrap().to_str().unwrap()),
+        name: normalize_name(&helper.file_name().unwrap_or_default().to_string_lossy()),
         return_type: None,
         input_type: Some(TsType::TsTypeLit(TsTypeLit {
             span: Default::default(),
             members: inputs,
         })),
-    });
+        doc: helper_doc(&module_exports_obj),
+        input_docs,
+        diagnostics: collector.diagnostics(),
+        dependencies,
+    })
 }
 
 pub struct SailsDeclHelperDirectory {
@@ -253,19 +328,77 @@ pub struct SailsDeclHelperDirectory {
 }
 
 pub enum SailsDeclHelperTreeNode {
-    Helper(SailsHelperInfo),
+    Helper(Box<SailsHelperInfo>),
     Directory(SailsDeclHelperDirectory),
 }
 
 pub struct SailsDeclHelperTree {
     root: Vec<SailsDeclHelperTreeNode>,
+    failures: Vec<(PathBuf, Diagnosed<GenHelperDeclError>)>,
 }
 
 impl SailsDeclHelperTree {
-    pub fn new(helpers: &Vec<PathBuf>, helpers_folder: &PathBuf, cm: Lrc<SourceMap>) -> Self {
-        SailsDeclHelperTree {
-            root: build_tree(helpers, helpers_folder, cm),
+    pub fn new(helpers: &[PathBuf], helpers_folders: &[PathBuf], cm: Lrc<SourceMap>, project: &ProjectConfig) -> Self {
+        let mut failures = Vec::new();
+        let root = build_tree(helpers, helpers_folders, cm, project, &mut failures);
+        SailsDeclHelperTree { root, failures }
+    }
+
+    /// Like [`SailsDeclHelperTree::new`], but only parses the helpers that are
+    /// not already in `cache`.
+    pub fn new_cached(
+        helpers: &[PathBuf],
+        helpers_folders: &[PathBuf],
+        cache: &mut HelperCache,
+        project: &ProjectConfig,
+    ) -> Self {
+        let mut failures = Vec::new();
+        let cm = cache.cm.clone();
+
+        // Parsing is most of the work, so the helpers that need it are parsed
+        // in parallel up front and the tree is then built from the results.
+        let cached = &cache.helpers;
+        let mut parsed: HashMap<PathBuf, _> = helpers
+            .par_iter()
+            .filter(|path| !cached.contains_key(*path))
+            .map(|path| (path.clone(), get_helper_info(path.clone(), cm.clone(), project)))
+            .collect();
+
+        let mut load = |path: PathBuf| {
+            if let Some(helper_info) = cache.helpers.get(&path) {
+                return Ok(helper_info.clone());
+            }
+            let helper_info = parsed
+                .remove(&path)
+                .unwrap_or_else(|| get_helper_info(path.clone(), cm.clone(), project))?;
+            cache.helpers.insert(path, helper_info.clone());
+            Ok(helper_info)
+        };
+        let root = build_tree_with(helpers, helpers_folders, &mut load, &mut failures);
+        SailsDeclHelperTree { root, failures }
+    }
+
+    /// Helpers that could not be included in the tree, and why.
+    pub fn get_failures(&self) -> &[(PathBuf, Diagnosed<GenHelperDeclError>)] {
+        &self.failures
+    }
+
+    /// Every diagnostic reported for the helpers in the tree, including the
+    /// ones that failed.
+    pub fn get_diagnostics(&self) -> Vec<Diagnostic> {
+        fn collect(nodes: &[SailsDeclHelperTreeNode], diagnostics: &mut Vec<Diagnostic>) {
+            for node in nodes {
+                match node {
+                    SailsDeclHelperTreeNode::Helper(helper) => diagnostics.extend(helper.diagnostics.iter().cloned()),
+                    SailsDeclHelperTreeNode::Directory(directory) => collect(&directory.children, diagnostics),
+                }
+            }
         }
+
+        let mut diagnostics = vec![];
+        collect(&self.root, &mut diagnostics);
+        diagnostics.extend(self.failures.iter().flat_map(|(_, failure)| failure.diagnostics.iter().cloned()));
+        diagnostics
     }
 
     pub fn get_root(&self) -> &Vec<SailsDeclHelperTreeNode> {
@@ -278,6 +411,21 @@ impl SailsDeclHelperTree {
         helpers
     }
 
+    pub fn get_all_helpers_mut(&mut self) -> Vec<&mut SailsHelperInfo> {
+        fn collect<'a>(nodes: &'a mut [SailsDeclHelperTreeNode], helpers: &mut Vec<&'a mut SailsHelperInfo>) {
+            for node in nodes {
+                match node {
+                    SailsDeclHelperTreeNode::Helper(helper_info) => helpers.push(helper_info),
+                    SailsDeclHelperTreeNode::Directory(dir) => collect(&mut dir.children, helpers),
+                }
+            }
+        }
+
+        let mut helpers = Vec::new();
+        collect(&mut self.root, &mut helpers);
+        helpers
+    }
+
     fn collect_helpers<'a>(
         &self,
         nodes: &'a Vec<SailsDeclHelperTreeNode>,
@@ -292,11 +440,34 @@ impl SailsDeclHelperTree {
     }
 }
 
-fn gen_decl_from_node(node: &SailsDeclHelperTreeNode) -> TsTypeElement {
+// Attaches each input's JSDoc to its member of the input type literal
+fn documented_input_type(helper_info: &SailsHelperInfo, depth: usize, docs: &DocComments) -> Option<TsType> {
+    let mut input_type = helper_info.input_type.clone()?;
+
+    if let TsType::TsTypeLit(type_lit) = &mut input_type {
+        for member in &mut type_lit.members {
+            let TsTypeElement::TsPropertySignature(signature) = member else {
+                continue;
+            };
+            let Some(name) = signature.key.as_lit().and_then(|lit| lit.as_str()).and_then(|s| s.value.as_str()) else {
+                continue;
+            };
+            let Some((_, doc)) = helper_info.input_docs.iter().find(|(input, _)| input == name) else {
+                continue;
+            };
+            signature.span = docs.attach(doc, depth, signature.span);
+        }
+    }
+
+    Some(input_type)
+}
+
+// `depth` is how deeply the node is nested in `HelpersObject`, for indenting its JSDoc
+fn gen_decl_from_node(node: &SailsDeclHelperTreeNode, depth: usize, docs: &DocComments) -> TsTypeElement {
     match node {
         SailsDeclHelperTreeNode::Helper(helper_info) => {
             TsTypeElement::TsPropertySignature(TsPropertySignature {
-                span: Default::default(),
+                span: docs.attach(&helper_info.doc, depth, Span::default()),
                 readonly: true,
                 key: Box::new(Expr::Lit(Lit::Str(Str {
                     span: Default::default(),
@@ -318,7 +489,7 @@ fn gen_decl_from_node(node: &SailsDeclHelperTreeNode) -> TsTypeElement {
                         type_params: Some(Box::new(TsTypeParamInstantiation {
                             span: Default::default(),
                             params: vec![
-                                Box::new(helper_info.input_type.clone().unwrap_or(TsType::TsKeywordType(
+                                Box::new(documented_input_type(helper_info, depth + 1, docs).unwrap_or(TsType::TsKeywordType(
                                     TsKeywordType {
                                         span: Default::default(),
                                         kind: swc_ecmascript::ast::TsKeywordTypeKind::TsAnyKeyword,
@@ -351,7 +522,10 @@ fn gen_decl_from_node(node: &SailsDeclHelperTreeNode) -> TsTypeElement {
                     span: Default::default(),
                     type_ann: Box::new(TsType::TsTypeLit(TsTypeLit {
                         span: Default::default(),
-                        members: children.iter().map(gen_decl_from_node).collect(),
+                        members: children
+                            .iter()
+                            .map(|child| gen_decl_from_node(child, depth + 1, docs))
+                            .collect(),
                     })),
                 })),
             })
@@ -359,15 +533,19 @@ fn gen_decl_from_node(node: &SailsDeclHelperTreeNode) -> TsTypeElement {
     }
 }
 
-fn gen_decl_from_tree(tree: SailsDeclHelperTree) -> TsType {
+fn gen_decl_from_tree(tree: SailsDeclHelperTree, docs: &DocComments) -> TsType {
     TsType::TsTypeLit(TsTypeLit {
         span: Default::default(),
-        members: tree.get_root().iter().map(gen_decl_from_node).collect(),
+        members: tree
+            .get_root()
+            .iter()
+            .map(|node| gen_decl_from_node(node, 1, docs))
+            .collect(),
     })
 }
 
-pub fn gen_helpers_object_decl(tree: SailsDeclHelperTree) -> TsTypeAliasDecl {
-    let decl = gen_decl_from_tree(tree);
+pub fn gen_helpers_object_decl(tree: SailsDeclHelperTree, docs: &DocComments) -> TsTypeAliasDecl {
+    let decl = gen_decl_from_tree(tree, docs);
 
     TsTypeAliasDecl {
         span: Default::default(),
@@ -389,31 +567,119 @@ pub struct ModelImport {
     pub import_path: String,
 }
 
+/// Helpers parsed by earlier runs, for processes that regenerate the helpers
+/// declaration more than once. Every helper is parsed into the same source
+/// map, so the cached spans stay valid for the emitter.
+#[derive(Default)]
+pub struct HelperCache {
+    cm: Lrc<SourceMap>,
+    helpers: HashMap<PathBuf, SailsHelperInfo>,
+}
+
+impl HelperCache {
+    /// Forgets `helper`, so it is parsed again next time. Returns whether it
+    /// was cached.
+    pub fn invalidate(&mut self, helper: &Path) -> bool {
+        self.helpers.remove(helper).is_some()
+    }
+
+    /// Forgets every helper, along with the source map they were parsed into.
+    pub fn clear(&mut self) {
+        *self = HelperCache::default();
+    }
+}
+
+/// The helpers declaration, and which helpers had to be left out of it.
+pub struct GeneratedHelpers {
+    /// Includes the diagnostics of the failed helpers.
+    pub emitted: EmittedCode,
+    pub failed: Vec<PathBuf>,
+    /// Files the declared helpers read through `require()`.
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// Declares `helpers`, named by their path relative to the first of
+/// `helpers_folders` they are in. When several folders have a helper with the
+/// same name, like an app and one of its hooks, the earlier folder's is used.
 pub fn generate_sails_helpers(
-    helpers: &Vec<PathBuf>,
-    helpers_folder: &PathBuf,
-    output_dts_path: &PathBuf
-) -> EmittedCode {
-    // 1. Create the master SourceMap that will hold ALL files
-    let cm: Lrc<SourceMap> = Default::default();
+    helpers: &[PathBuf],
+    helpers_folders: &[PathBuf],
+    output_dts_path: &Path,
+    project: &ProjectConfig,
+    models: &ModelIndex,
+) -> Result<GeneratedHelpers, Diagnosed<GenHelperDeclError>> {
+    generate_sails_helpers_cached(helpers, helpers_folders, output_dts_path, project, models, &mut HelperCache::default())
+}
 
-    // 2. Build the tree, passing the shared 'cm'
-    // You'll need to update build_tree and get_helper_info to accept &cm
-    let tree = SailsDeclHelperTree::new(helpers, helpers_folder, cm.clone());
-    let decl = gen_helpers_object_decl(tree);
+/// Like [`generate_sails_helpers`], but reuses the helpers in `cache` and adds
+/// the ones it had to parse.
+pub fn generate_sails_helpers_cached(
+    helpers: &[PathBuf],
+    helpers_folders: &[PathBuf],
+    output_dts_path: &Path,
+    project: &ProjectConfig,
+    models: &ModelIndex,
+    cache: &mut HelperCache,
+) -> Result<GeneratedHelpers, Diagnosed<GenHelperDeclError>> {
+    // 1. The cache holds the master SourceMap that will hold ALL files
+    let cm = cache.cm.clone();
+
+    // 2. Build the tree, parsing into the shared 'cm'
+    let mut tree = SailsDeclHelperTree::new_cached(helpers, helpers_folders, cache, project);
+    let diagnostics = tree.get_diagnostics();
+    let failed = tree.get_failures().iter().map(|(path, _)| path.clone()).collect();
+    let mut dependencies: Vec<PathBuf> = tree
+        .get_all_helpers()
+        .into_iter()
+        .flat_map(|helper| helper.dependencies.iter().cloned())
+        .collect();
+    dependencies.sort();
+    dependencies.dedup();
+    if project.strict_type_hints
+        && tree
+            .get_failures()
+            .iter()
+            .any(|(_, e)| matches!(e.error, GenHelperDeclError::SDTypeHintParseError))
+    {
+        return Err(Diagnosed {
+            error: GenHelperDeclError::SDTypeHintParseError,
+            diagnostics,
+        });
+    }
+
+    let mut available = models.type_imports();
+    available.extend(project.type_imports.iter().cloned());
+    let hint_types = tree
+        .get_all_helpers_mut()
+        .into_iter()
+        .flat_map(|helper| {
+            let source_dir = helper.path.parent();
+            [helper.input_type.as_mut(), helper.return_type.as_mut()]
+                .into_iter()
+                .flatten()
+                .map(move |ts_type| (ts_type, source_dir))
+        })
+        .collect();
+    let imports = resolve_type_references(hint_types, output_dts_path, &available);
+
+    let docs = DocComments::default();
+    let decl = gen_helpers_object_decl(tree, &docs);
 
     let module = Module {
         span: Default::default(),
-        body: vec![
-            ModuleItem::ModuleDecl(swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
-                span: Default::default(),
-                decl: Decl::TsInterface(Box::new(get_helper_object_interface())),
-            })),
-            ModuleItem::ModuleDecl(swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
-                span: Default::default(),
-                decl: Decl::TsTypeAlias(Box::new(decl)),
-            })),
-        ],
+        body: imports
+            .into_iter()
+            .chain([
+                ModuleItem::ModuleDecl(swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
+                    span: Default::default(),
+                    decl: Decl::TsInterface(Box::new(get_helper_object_interface())),
+                })),
+                ModuleItem::ModuleDecl(swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
+                    span: Default::default(),
+                    decl: Decl::TsTypeAlias(Box::new(decl)),
+                })),
+            ])
+            .collect(),
         shebang: None,
     };
 
@@ -422,11 +688,11 @@ pub fn generate_sails_helpers(
 
     // 3. Emit the aggregated AST
     {
-        let writer = JsWriter::new(cm.clone(), "\n", &mut buf, Some(&mut src_map_buf));
+        let writer = DocWriter::new(JsWriter::new(cm.clone(), "\n", &mut buf, Some(&mut src_map_buf)));
         let mut emitter = Emitter {
             cfg: Config::default().with_minify(false),
             cm: cm.clone(),
-            comments: None,
+            comments: Some(docs.comments()),
             wr: writer,
         };
 
@@ -436,7 +702,8 @@ pub fn generate_sails_helpers(
     let mut code = String::from_utf8(buf).expect("utf8");
 
     // 4. Generate the multi-source map
-    let mut sourcemap = cm.build_source_map(&src_map_buf, None, DefaultSourceMapGenConfig {});
+    let output_dir = output_dts_path.parent().unwrap_or(Path::new(""));
+    let mut sourcemap = cm.build_source_map(&src_map_buf, None, RelativeSources { output_dir });
     
     // Set the output filename so the LSP knows which file this map belongs to
     let dts_name = output_dts_path.file_name().map(|n| n.to_string_lossy().into_owned());
@@ -450,19 +717,28 @@ pub fn generate_sails_helpers(
     let map_file_name = format!("{}.map", output_dts_path.file_name().unwrap().to_str().unwrap());
     code.push_str(&format!("\n//# sourceMappingURL={}", map_file_name));
 
-    EmittedCode { 
-        code, 
-        source_map: source_map_json 
-    }
+    Ok(GeneratedHelpers {
+        emitted: EmittedCode {
+            code,
+            source_map: source_map_json,
+            diagnostics,
+        },
+        failed,
+        dependencies,
+    })
 }
 
 pub fn generate_global_declarations_file(
     models: &[ModelImport],
     helpers_import_path: &str,
-    output_dts_path: &PathBuf,
+    output_dts_path: &Path,
 ) -> EmittedCode {
     let cm: Lrc<SourceMap> = Default::default();
 
+    // Sorted by name, so the output does not depend on the order models were found in
+    let mut models: Vec<&ModelImport> = models.iter().collect();
+    models.sort_by(|a, b| a.model_name.cmp(&b.model_name));
+
     let model_info: Vec<SailsModelInfo> = models
         .iter()
         .map(|model| SailsModelInfo {
@@ -509,7 +785,8 @@ pub fn generate_global_declarations_file(
 
     let mut code = String::from_utf8(buf).expect("utf8");
 
-    let mut sourcemap = cm.build_source_map(&src_map_buf, None, DefaultSourceMapGenConfig {});
+    let output_dir = output_dts_path.parent().unwrap_or(Path::new(""));
+    let mut sourcemap = cm.build_source_map(&src_map_buf, None, RelativeSources { output_dir });
     let dts_name = output_dts_path.file_name().map(|n| n.to_string_lossy().into_owned());
     sourcemap.set_file(dts_name);
 
@@ -523,5 +800,6 @@ pub fn generate_global_declarations_file(
     EmittedCode {
         code,
         source_map: source_map_json,
+        diagnostics: vec![],
     }
 }
@@ -1,4 +1,10 @@
+pub mod config;
+pub mod diagnostics;
+pub mod docs;
 pub(crate) mod literal_declarations;
 pub mod model;
 pub mod helpers;
+pub mod references;
+pub(crate) mod resolver;
+pub mod source;
 mod util;
\ No newline at end of file
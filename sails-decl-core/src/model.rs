@@ -1,24 +1,39 @@
 extern crate swc_common;
 extern crate swc_ecma_parser;
 
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::path::{Path, PathBuf};
 
-use swc_common::Spanned;
-use swc_common::source_map::{DefaultSourceMapGenConfig};
+use swc_common::comments::{SingleThreadedComments, SingleThreadedCommentsMapInner};
+use swc_common::errors::DiagnosticId;
+use swc_common::{Span, Spanned};
 use swc_common::sync::Lrc;
 use swc_common::{
     FileName, SourceMap,
-    errors::{ColorConfig, Handler},
 };
 use swc_ecma_codegen::{Config, Emitter};
 use swc_ecma_codegen::text_writer::JsWriter;
-use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
 use swc_ecmascript::ast::{
-    Decl, ExportDecl, Expr, Ident, Lit, Module, ModuleItem, Str, TsInterfaceBody,
-    TsInterfaceDecl, TsPropertySignature, TsTypeAnn, TsTypeElement,
+    BindingIdent, BlockStmtOrExpr, Decl, ExportDecl, Expr, Ident, KeyValueProp, Lit, Module,
+    ModuleItem, ObjectLit, Prop, PropName, PropOrSpread, Stmt, Str, TsEntityName, TsFnParam, TsIndexSignature, TsInterfaceBody,
+    TsInterfaceDecl, TsKeywordTypeKind, TsLit, TsLitType, TsPropertySignature, TsType,
+    TsTypeAliasDecl, TsTypeAnn, TsTypeElement, TsTypeLit, TsTypeParamInstantiation, TsTypeRef,
+    TsUnionOrIntersectionType, TsUnionType, UnaryOp,
 };
 
-use crate::util::{EmittedCode, find_module_exports, get_prop_as_str, ts_type_from_attribute};
+use crate::docs::{DocComments, DocWriter, attribute_doc};
+use crate::config::{ModelSettings, ProjectConfig, SailsVersion};
+use crate::references::{ImportSource, TypeImport, resolve_type_references};
+use crate::resolver::{ModuleResolver, Scope};
+use crate::diagnostics::{Diagnosed, Diagnostic, DiagnosticCollector, codes};
+use crate::source::parse_source_file;
+use crate::util::{
+    EmittedCode, RelativeSources, emit_type_hint_error, find_module_exports_expr, type_ref, get_bool_prop, get_object_prop, get_prop_as_str, get_str_prop, keyword_type,
+    ts_type_from_attribute, with_jsdoc_type_hint,
+};
 #[derive(Debug)]
 pub enum GenDeclarationsError {
     ParseError,
@@ -28,69 +43,439 @@ pub enum GenDeclarationsError {
     SDTypeHintParseError,
 }
 
+impl std::fmt::Display for GenDeclarationsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GenDeclarationsError::ParseError => "failed to parse model",
+            GenDeclarationsError::InvalidModel => "model does not define an `attributes` object",
+            GenDeclarationsError::IsNotCommonJsModule => "model does not assign `module.exports`",
+            GenDeclarationsError::CommonJsModuleDoesNotExportObject => "model does not export an object",
+            GenDeclarationsError::SDTypeHintParseError => "model has an invalid `$SD-type-hint`",
+        })
+    }
+}
+
+impl std::error::Error for GenDeclarationsError {}
+
 pub struct ModelDecl {
     module: Module,
+    docs: DocComments,
     source_map: Lrc<SourceMap>,
+    /// Problems found in the model that did not stop it from being declared.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Sails derives a model's identity (used by `model: '...'` associations) from
+/// its file name, lowercased.
+pub fn model_identity(model_name: &str) -> String {
+    model_name.to_lowercase()
+}
+
+pub struct PrimaryKey {
+    pub attribute: String,
+    pub ts_type: TsType,
 }
 
-pub fn model_type_name(model_name: &str) -> String {
-    format!("{}__ModelDecl", model_name)
+/// A model definition that has been parsed but not yet turned into a declaration.
+#[derive(Clone)]
+pub struct ParsedModel {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    definition: ObjectLit,
+    attributes: ObjectLit,
+    /// Kept as a plain map rather than `SingleThreadedComments` so parsed models
+    /// can be sent to other threads.
+    leading_comments: SingleThreadedCommentsMapInner,
+    source_map: Lrc<SourceMap>,
+    /// Problems found while parsing, such as recovered syntax errors.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Files read through `require()` while resolving the definition.
+    pub dependencies: Vec<PathBuf>,
 }
 
-pub fn gen_decl(code: String, model_name: String, file_path: Option<PathBuf>) -> Result<ModelDecl, GenDeclarationsError> {
+pub fn parse_model(
+    code: String,
+    model_name: String,
+    file_path: Option<PathBuf>,
+) -> Result<ParsedModel, Diagnosed<GenDeclarationsError>> {
     let cm: Lrc<SourceMap> = Default::default();
-    let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
+    let collector = DiagnosticCollector::new(cm.clone(), file_path.clone());
+    let handler = collector.handler();
 
-    let file = cm.new_source_file(match file_path {
+    let file = cm.new_source_file(match file_path.clone() {
         Some(path) => FileName::Real(path),
         None => FileName::Anon,
      }.into(), code);
 
-    let lexer = Lexer::new(
-        Syntax::Es(Default::default()),
-        Default::default(),
-        StringInput::from(&*file),
-        None,
-    );
+    // Comments are kept so JSDoc `@type` tags can serve as type hints
+    let comments = SingleThreadedComments::default();
+
+    let module = parse_source_file(&file, Some(&comments), handler)
+        .ok_or_else(|| collector.fail(GenDeclarationsError::ParseError, codes::SYNTAX))?;
+
+    let module_exports = find_module_exports_expr(&module)
+        .ok_or_else(|| collector.fail(GenDeclarationsError::IsNotCommonJsModule, codes::NO_EXPORTS))?;
 
-    let mut parser = Parser::new_from(lexer);
+    // Shared attributes are commonly spread in from local constants or
+    // `require()`d files, so both objects are flattened before use
+    let mut resolver = ModuleResolver::new(cm.clone(), &comments, handler);
+    let scope = Scope {
+        script: &module,
+        path: file_path.as_deref(),
+    };
+
+    let module_exports_obj = resolver
+        .resolve_object(&module_exports, scope)
+        .ok_or_else(|| collector.fail(GenDeclarationsError::CommonJsModuleDoesNotExportObject, codes::EXPORTS_NOT_OBJECT))?;
+    let dependencies = resolver.dependencies();
+    drop(resolver);
+
+    let attributes_obj = get_object_prop(&module_exports_obj, "attributes")
+        .cloned()
+        .ok_or_else(|| collector.fail(GenDeclarationsError::InvalidModel, codes::MISSING_ATTRIBUTES))?;
+
+    Ok(ParsedModel {
+        name: model_name,
+        path: file_path,
+        definition: module_exports_obj,
+        attributes: attributes_obj,
+        leading_comments: comments.take_all().0.take(),
+        source_map: cm,
+        diagnostics: collector.diagnostics(),
+        dependencies,
+    })
+}
 
-    for e in parser.take_errors() {
-        e.into_diagnostic(&handler).emit();
+impl ParsedModel {
+    pub fn identity(&self) -> String {
+        model_identity(&self.name)
     }
 
-    let module_result = parser.parse_commonjs().map_err(|e| {
-        // Unrecoverable fatal error occurred
-        e.into_diagnostic(&handler).emit()
-    });
+    /// Resolves which attribute is the primary key and what type its values have.
+    ///
+    /// An explicit `type` on the key attribute wins, except on `sails-mongo`
+    /// datastores where ObjectIds are always exposed as strings unless
+    /// `dontUseObjectIds` is set. Without a `type`, keys are autoincrement numbers.
+    pub fn primary_key(&self, project: &ProjectConfig) -> PrimaryKey {
+        let settings = ModelSettings::from_object(&self.definition);
+
+        let attribute = settings
+            .primary_key
+            .or_else(|| project.models.primary_key.clone())
+            .unwrap_or_else(|| "id".to_string());
+
+        let datastore = settings.datastore.as_deref().or(project.models.datastore.as_deref());
+        let uses_object_ids = project.adapter_for(datastore) == "sails-mongo"
+            && !settings
+                .dont_use_object_ids
+                .or(project.models.dont_use_object_ids)
+                .unwrap_or(false);
+
+        let declared_type = self
+            .find_attribute(&attribute)
+            .or_else(|| {
+                project
+                    .models
+                    .attributes
+                    .iter()
+                    .find(|(name, _)| *name == attribute)
+                    .map(|(_, obj)| obj)
+            })
+            .and_then(|obj| get_str_prop(obj, "type"));
+
+        let ts_type = match declared_type {
+            _ if uses_object_ids => keyword_type(TsKeywordTypeKind::TsStringKeyword),
+            Some("string" | "text" | "objectid") => keyword_type(TsKeywordTypeKind::TsStringKeyword),
+            _ => keyword_type(TsKeywordTypeKind::TsNumberKeyword),
+        };
+
+        PrimaryKey { attribute, ts_type }
+    }
 
-    if module_result.is_err() {
-        return Err(GenDeclarationsError::ParseError);
+    /// What the [`ModelIndex`] needs to know about the model.
+    pub fn summary(&self, project: &ProjectConfig) -> ModelSummary {
+        let primary_key = self.primary_key(project);
+        let string_primary_key = matches!(
+            &primary_key.ts_type,
+            TsType::TsKeywordType(keyword) if keyword.kind == TsKeywordTypeKind::TsStringKeyword
+        );
+        ModelSummary {
+            name: self.name.clone(),
+            primary_key: primary_key.attribute,
+            string_primary_key,
+        }
     }
 
-    let module = module_result.unwrap();
+    /// Whether the model is schemaless (`schema: false`), in which case records
+    /// may carry arbitrary keys besides the declared attributes.
+    pub fn is_schemaless(&self, project: &ProjectConfig) -> bool {
+        get_bool_prop(&self.definition, "schema")
+            .or(project.models.schema)
+            .is_some_and(|schema| !schema)
+    }
 
-    let module_exports_obj =
-        find_module_exports(module).ok_or(GenDeclarationsError::IsNotCommonJsModule)?;
+    fn find_attribute(&self, name: &str) -> Option<&ObjectLit> {
+        get_object_prop(&self.attributes, name)
+    }
 
-    let mut elements: Vec<TsTypeElement> = vec![];
+    fn declares_attribute(&self, name: &str) -> bool {
+        self.attributes.props.iter().any(|prop| {
+            prop.as_prop()
+                .and_then(|p| p.as_key_value())
+                .and_then(|p| get_prop_as_str(&p.key))
+                == Some(name)
+        })
+    }
+}
 
-    let attributes_obj = module_exports_obj
-        .props
-        .iter()
-        .find_map(|prop| {
-            let _key_value_prop = prop.as_prop().and_then(|p| p.as_key_value())?;
-            let key_name = get_prop_as_str(&_key_value_prop.key)?;
+/// Finds the attribute names a simple `customToJSON` strips from the record,
+/// i.e. `return _.omit(this, [...])` (or the variadic form) and `delete this.x;`.
+fn custom_to_json_omits(definition: &ObjectLit) -> Vec<String> {
+    let Some(prop) = definition.props.iter().find_map(|prop| {
+        let prop = prop.as_prop()?;
+        let key = match &**prop {
+            Prop::KeyValue(kv) => &kv.key,
+            Prop::Method(method) => &method.key,
+            _ => return None,
+        };
+        (get_prop_as_str(key)? == "customToJSON").then_some(&**prop)
+    }) else {
+        return vec![];
+    };
 
-            if key_name != "attributes" {
-                return None;
+    let (stmts, expr_body): (&[Stmt], Option<&Expr>) = match prop {
+        Prop::Method(method) => match &method.function.body {
+            Some(body) => (&body.stmts, None),
+            None => return vec![],
+        },
+        Prop::KeyValue(kv) => match &*kv.value {
+            Expr::Fn(fn_expr) => match &fn_expr.function.body {
+                Some(body) => (&body.stmts, None),
+                None => return vec![],
+            },
+            Expr::Arrow(arrow) => match &*arrow.body {
+                BlockStmtOrExpr::BlockStmt(body) => (&body.stmts, None),
+                BlockStmtOrExpr::Expr(expr) => (&[], Some(&**expr)),
+            },
+            _ => return vec![],
+        },
+        _ => return vec![],
+    };
+
+    let mut omitted = vec![];
+
+    if let Some(expr) = expr_body {
+        omitted.extend(lodash_omit_keys(expr));
+    }
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::Return(ret) => {
+                if let Some(arg) = &ret.arg {
+                    omitted.extend(lodash_omit_keys(arg));
+                }
+            }
+            Stmt::Expr(expr_stmt) => {
+                let Some(unary) = expr_stmt.expr.as_unary() else {
+                    continue;
+                };
+                if unary.op != UnaryOp::Delete {
+                    continue;
+                }
+                let Some(member) = unary.arg.as_member() else {
+                    continue;
+                };
+                if !member.obj.is_this() {
+                    continue;
+                }
+                if let Some(ident) = member.prop.as_ident() {
+                    omitted.push(ident.sym.to_string());
+                } else if let Some(name) = member
+                    .prop
+                    .as_computed()
+                    .and_then(|computed| computed.expr.as_lit()?.as_str()?.value.as_str())
+                {
+                    omitted.push(name.to_string());
+                }
             }
+            _ => {}
+        }
+    }
+
+    omitted
+}
 
-            _key_value_prop.value.as_object().cloned()
+// _.omit(this, ['a', 'b']) or _.omit(this, 'a', 'b')
+fn lodash_omit_keys(expr: &Expr) -> Vec<String> {
+    let Some(call) = expr.as_call() else {
+        return vec![];
+    };
+    let Some(callee) = call.callee.as_expr().and_then(|callee| callee.as_member()) else {
+        return vec![];
+    };
+    if callee.obj.as_ident().map(|ident| ident.sym.as_str()) != Some("_")
+        || callee.prop.as_ident().map(|ident| ident.sym.as_str()) != Some("omit")
+    {
+        return vec![];
+    }
+    if !call.args.first().is_some_and(|arg| arg.expr.is_this()) {
+        return vec![];
+    }
+
+    let str_value = |expr: &Expr| {
+        expr.as_lit()?
+            .as_str()?
+            .value
+            .as_str()
+            .map(str::to_string)
+    };
+
+    call.args
+        .iter()
+        .skip(1)
+        .flat_map(|arg| match &*arg.expr {
+            Expr::Array(array) => array
+                .elems
+                .iter()
+                .flatten()
+                .filter_map(|elem| str_value(&elem.expr))
+                .collect(),
+            expr => str_value(expr).into_iter().collect::<Vec<_>>(),
         })
-        .ok_or(GenDeclarationsError::InvalidModel)?;
+        .collect()
+}
+
+/// What the [`ModelIndex`] knows about a model, in a form that can be stored
+/// between runs so unchanged models need not be parsed again.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModelSummary {
+    pub name: String,
+    pub primary_key: String,
+    /// Whether primary key values are strings rather than numbers.
+    pub string_primary_key: bool,
+}
+
+pub struct IndexedModel {
+    pub name: String,
+    /// Name of the interface declared for the model.
+    pub type_name: String,
+    pub primary_key: PrimaryKey,
+    /// Path of the generated `.d.ts` for the model.
+    pub declaration_path: PathBuf,
+}
+
+/// What is known about every model of the project, used to resolve
+/// associations and type hints that refer to other models.
+#[derive(Default)]
+pub struct ModelIndex {
+    /// Keyed by identity and sorted, so `type_imports` is stable.
+    models: BTreeMap<String, IndexedModel>,
+}
+
+impl ModelIndex {
+    pub fn insert(&mut self, model: &ParsedModel, project: &ProjectConfig, declaration_path: PathBuf) {
+        self.insert_summary(&model.summary(project), project, declaration_path);
+    }
+
+    pub fn insert_summary(&mut self, summary: &ModelSummary, project: &ProjectConfig, declaration_path: PathBuf) {
+        let key_type = match summary.string_primary_key {
+            true => TsKeywordTypeKind::TsStringKeyword,
+            false => TsKeywordTypeKind::TsNumberKeyword,
+        };
+        self.models.insert(
+            model_identity(&summary.name),
+            IndexedModel {
+                name: summary.name.clone(),
+                type_name: project.naming.model_type(&summary.name),
+                primary_key: PrimaryKey {
+                    attribute: summary.primary_key.clone(),
+                    ts_type: keyword_type(key_type),
+                },
+                declaration_path,
+            },
+        );
+    }
+
+    pub fn get(&self, identity: &str) -> Option<&IndexedModel> {
+        self.models.get(&model_identity(identity))
+    }
+
+    /// Lets hints refer to each model by name, e.g. `Array<User>`.
+    pub fn type_imports(&self) -> Vec<TypeImport> {
+        self.models
+            .values()
+            .map(|model| TypeImport {
+                name: model.name.clone(),
+                imported: Some(model.type_name.clone()),
+                source: ImportSource::Path(model.declaration_path.with_extension("").with_extension("")),
+            })
+            .collect()
+    }
+}
+
+/// `string | number`, for associations whose target's primary key is unknown.
+fn unknown_primary_key() -> TsType {
+    TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(TsUnionType {
+        span: Default::default(),
+        types: vec![
+            Box::new(keyword_type(TsKeywordTypeKind::TsStringKeyword)),
+            Box::new(keyword_type(TsKeywordTypeKind::TsNumberKeyword)),
+        ],
+    }))
+}
 
-    for attribute in &attributes_obj.props {
+fn attribute_signature(key: &str, span: Span, optional: bool, ts_type: TsType) -> TsTypeElement {
+    TsTypeElement::TsPropertySignature(TsPropertySignature {
+        span,
+        readonly: false,
+        key: Box::new(Expr::Lit(Lit::Str(Str {
+            span,
+            value: key.into(),
+            raw: None,
+        }))),
+        computed: true,
+        optional,
+        type_ann: Some(Box::new(TsTypeAnn {
+            span: Default::default(),
+            type_ann: Box::new(ts_type),
+        })),
+    })
+}
+
+/// Generates the declaration for `model`, to be written to `declaration_path`.
+/// `models` is used to type singular (`model: '...'`) associations and to import
+/// the models and project types referenced by type hints.
+pub fn gen_decl(
+    mut model: ParsedModel,
+    project: &ProjectConfig,
+    models: &ModelIndex,
+    declaration_path: &Path,
+) -> Result<ModelDecl, Diagnosed<GenDeclarationsError>> {
+    let primary_key = model.primary_key(project);
+    let collector = DiagnosticCollector::new(model.source_map.clone(), model.path.clone());
+    let handler = collector.handler();
+    let mut type_hint_errors = false;
+    let docs = DocComments::default();
+    let comments = SingleThreadedComments::from_leading_and_trailing(
+        Rc::new(RefCell::new(std::mem::take(&mut model.leading_comments))),
+        Default::default(),
+    );
+
+    let mut elements: Vec<TsTypeElement> = vec![];
+    let mut json_omitted = custom_to_json_omits(&model.definition);
+
+    // Sails merges the default attributes from `config/models.js` into every
+    // model, with the model's own definitions (including `id: false`) taking precedence.
+    let mut attributes: Vec<(&str, Span, Cow<ObjectLit>)> = project
+        .models
+        .attributes
+        .iter()
+        .filter(|(name, _)| !model.declares_attribute(name))
+        .map(|(name, obj)| (name.as_str(), Span::default(), Cow::Borrowed(obj)))
+        .collect();
+
+    for attribute in &model.attributes.props {
         let attribute_pair = match attribute.as_prop().and_then(|p| p.as_key_value()) {
             Some(prop) => prop,
             None => continue,
@@ -101,39 +486,183 @@ pub fn gen_decl(code: String, model_name: String, file_path: Option<PathBuf>) ->
             None => continue,
         };
 
-        let _attr_value_obj = match attribute_pair.value.as_object() {
-            Some(obj) => obj,
-            None => continue,
+        let _attr_value_obj = match &*attribute_pair.value {
+            Expr::Object(obj) => Cow::Borrowed(obj),
+            // Sails 0.12 allows `name: 'string'` as shorthand for `name: { type: 'string' }`
+            Expr::Lit(Lit::Str(type_name)) if project.sails_version == SailsVersion::Legacy => {
+                Cow::Owned(ObjectLit {
+                    span: type_name.span,
+                    props: vec![PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                        key: PropName::Ident("type".into()),
+                        value: Box::new(Expr::Lit(Lit::Str(type_name.clone()))),
+                    })))],
+                })
+            }
+            _ => continue,
         };
 
-        let attribute_type_info = match ts_type_from_attribute(_attr_value_obj) {
-            Some(info) => info,
-            None => continue,
+        let _attr_value_obj =
+            with_jsdoc_type_hint(_attr_value_obj, &comments, attribute_pair.key.span().lo);
+
+        attributes.push((_attr_key_ident.sym.as_str(), attribute_pair.key.span(), _attr_value_obj));
+    }
+
+    // Sails 0.12 adds the primary key and timestamps itself unless told not to.
+    if project.sails_version == SailsVersion::Legacy {
+        let settings = ModelSettings::from_object(&model.definition);
+        let enabled = |model_value: Option<bool>, default_value: Option<bool>| {
+            model_value.or(default_value).unwrap_or(true)
+        };
+        let declared = |name: &str| {
+            model.declares_attribute(name) || project.models.attributes.iter().any(|(default, _)| default == name)
         };
 
-        elements.push(TsTypeElement::TsPropertySignature(TsPropertySignature {
-            span: attribute_pair.key.span(),
-            readonly: false,
-            key: Box::new(Expr::Lit(Lit::Str(Str {
-                span: attribute_pair.key.span(),
-                value: _attr_key_ident.sym.as_str().into(),
-                raw: None,
-            }))),
-            computed: true,
-            optional: !attribute_type_info.required,
+        if enabled(settings.auto_pk, project.models.auto_pk)
+            && !declared(&primary_key.attribute)
+        {
+            elements.push(attribute_signature(
+                &primary_key.attribute,
+                Span::default(),
+                false,
+                primary_key.ts_type.clone(),
+            ));
+        }
+        for (name, enabled) in [
+            ("createdAt", enabled(settings.auto_created_at, project.models.auto_created_at)),
+            ("updatedAt", enabled(settings.auto_updated_at, project.models.auto_updated_at)),
+        ] {
+            if enabled && !declared(name) {
+                elements.push(attribute_signature(name, Span::default(), false, type_ref("Date")));
+            }
+        }
+    }
+
+    for (name, span, attribute) in attributes {
+        let attribute = attribute.as_ref();
+        let span = docs.attach(&attribute_doc(attribute), 1, span);
+        let stripped_from_json = get_bool_prop(attribute, "encrypt").unwrap_or(false)
+            || get_bool_prop(attribute, "protect").unwrap_or(false);
+        if stripped_from_json && !json_omitted.iter().any(|omitted| omitted == name) {
+            json_omitted.push(name.to_string());
+        }
+
+        if name == primary_key.attribute {
+            elements.push(attribute_signature(name, span, false, primary_key.ts_type.clone()));
+            continue;
+        }
+
+        if let Some(target) = get_str_prop(attribute, "model") {
+            let ts_type = match models.get(target) {
+                Some(target) => target.primary_key.ts_type.clone(),
+                None => {
+                    let message = format!("unknown model `{}` in association `{}`", target, name);
+                    let mut diagnostic = match span.is_dummy() {
+                        true => handler.struct_warn(&message),
+                        false => handler.struct_span_warn(span, &message),
+                    };
+                    diagnostic.code(DiagnosticId::Error(codes::UNKNOWN_MODEL.into()));
+                    diagnostic.note("typed as any primary key");
+                    diagnostic.emit();
+                    unknown_primary_key()
+                }
+            };
+            let required = get_bool_prop(attribute, "required").unwrap_or(false);
+            elements.push(attribute_signature(name, span, !required, ts_type));
+            continue;
+        }
+
+        let attribute_type_info = match ts_type_from_attribute(attribute, project.sails_version, &project.types) {
+            Ok(Some(info)) => info,
+            Ok(None) => continue,
+            Err(mut error) => {
+                if span.is_dummy() {
+                    error.span = Span::default();
+                }
+                emit_type_hint_error(handler, &error, project.strict_type_hints);
+                type_hint_errors = true;
+                continue;
+            }
+        };
+
+        elements.push(attribute_signature(
+            name,
+            span,
+            !attribute_type_info.required,
+            attribute_type_info.ts_type,
+        ));
+    }
+
+    if type_hint_errors && project.strict_type_hints {
+        let mut diagnostics = model.diagnostics;
+        diagnostics.extend(collector.diagnostics());
+        return Err(Diagnosed {
+            error: GenDeclarationsError::SDTypeHintParseError,
+            diagnostics,
+        });
+    }
+
+    // [key: string]: unknown;
+    if model.is_schemaless(project) {
+        elements.push(TsTypeElement::TsIndexSignature(TsIndexSignature {
+            span: Default::default(),
+            params: vec![TsFnParam::Ident(BindingIdent {
+                id: Ident {
+                    span: Default::default(),
+                    ctxt: Default::default(),
+                    sym: "key".into(),
+                    optional: false,
+                },
+                type_ann: Some(Box::new(TsTypeAnn {
+                    span: Default::default(),
+                    type_ann: Box::new(keyword_type(TsKeywordTypeKind::TsStringKeyword)),
+                })),
+            })],
             type_ann: Some(Box::new(TsTypeAnn {
                 span: Default::default(),
-                type_ann: Box::new(attribute_type_info.ts_type),
+                type_ann: Box::new(keyword_type(TsKeywordTypeKind::TsUnknownKeyword)),
             })),
+            readonly: false,
+            is_static: false,
         }));
     }
 
+    let mut available = models.type_imports();
+    available.extend(project.type_imports.iter().cloned());
+    let source_dir = model.path.as_deref().and_then(Path::parent);
+    let hint_types = elements
+        .iter_mut()
+        .filter_map(|element| match element {
+            TsTypeElement::TsPropertySignature(signature) => {
+                Some((&mut *signature.type_ann.as_mut()?.type_ann, source_dir))
+            }
+            _ => None,
+        })
+        .collect();
+    let imports = resolve_type_references(hint_types, declaration_path, &available);
+
+    // `Omit` over a type with an index signature keeps only the signature, so
+    // the JSON type of a schemaless model lists the remaining members itself
+    let json_members: Option<Vec<TsTypeElement>> = (model.is_schemaless(project) && !json_omitted.is_empty())
+        .then(|| {
+            elements
+                .iter()
+                .filter(|element| match element {
+                    TsTypeElement::TsPropertySignature(signature) => !matches!(
+                        &*signature.key,
+                        Expr::Lit(Lit::Str(key)) if json_omitted.iter().any(|name| key.value == *name.as_str())
+                    ),
+                    _ => true,
+                })
+                .cloned()
+                .collect()
+        });
+
     let interface_decl = TsInterfaceDecl {
         span: Default::default(),
         id: Ident {
             span: Default::default(),
             ctxt: Default::default(),
-            sym: model_type_name(&model_name).into(),
+            sym: project.naming.model_type(&model.name).into(),
             optional: false,
         },
         declare: true,
@@ -145,31 +674,134 @@ pub fn gen_decl(code: String, model_name: String, file_path: Option<PathBuf>) ->
         },
     };
 
-    Ok(ModelDecl {
-        module: Module {
+    // export declare type Name__PrimaryKey = "id";
+    let primary_key_decl = TsTypeAliasDecl {
+        span: Default::default(),
+        declare: true,
+        id: Ident {
+            span: Default::default(),
+            ctxt: Default::default(),
+            sym: project.naming.primary_key_type(&model.name).into(),
+            optional: false,
+        },
+        type_params: None,
+        type_ann: Box::new(TsType::TsLitType(TsLitType {
+            span: Default::default(),
+            lit: TsLit::Str(Str {
+                span: Default::default(),
+                value: primary_key.attribute.as_str().into(),
+                raw: None,
+            }),
+        })),
+    };
+
+    // export declare type Name__JSON = Omit<Name__ModelDecl, "password" | ...>;
+    // or, for schemaless models, { name: string; [key: string]: unknown; }
+    let model_type_ref = TsType::TsTypeRef(TsTypeRef {
+        span: Default::default(),
+        type_name: TsEntityName::Ident(Ident {
             span: Default::default(),
-            body: vec![ModuleItem::ModuleDecl(
-                swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
+            ctxt: Default::default(),
+            sym: project.naming.model_type(&model.name).into(),
+            optional: false,
+        }),
+        type_params: None,
+    });
+    let json_decl = TsTypeAliasDecl {
+        span: Default::default(),
+        declare: true,
+        id: Ident {
+            span: Default::default(),
+            ctxt: Default::default(),
+            sym: project.naming.json_type(&model.name).into(),
+            optional: false,
+        },
+        type_params: None,
+        type_ann: Box::new(if let Some(members) = json_members {
+            TsType::TsTypeLit(TsTypeLit {
+                span: Default::default(),
+                members,
+            })
+        } else if json_omitted.is_empty() {
+            model_type_ref
+        } else {
+            TsType::TsTypeRef(TsTypeRef {
+                span: Default::default(),
+                type_name: TsEntityName::Ident(Ident {
                     span: Default::default(),
-                    decl: Decl::TsInterface(Box::new(interface_decl)),
+                    ctxt: Default::default(),
+                    sym: "Omit".into(),
+                    optional: false,
                 }),
-            )],
+                type_params: Some(Box::new(TsTypeParamInstantiation {
+                    span: Default::default(),
+                    params: vec![
+                        Box::new(model_type_ref),
+                        Box::new(TsType::TsUnionOrIntersectionType(
+                            TsUnionOrIntersectionType::TsUnionType(TsUnionType {
+                                span: Default::default(),
+                                types: json_omitted
+                                    .iter()
+                                    .map(|name| {
+                                        Box::new(TsType::TsLitType(TsLitType {
+                                            span: Default::default(),
+                                            lit: TsLit::Str(Str {
+                                                span: Default::default(),
+                                                value: name.as_str().into(),
+                                                raw: None,
+                                            }),
+                                        }))
+                                    })
+                                    .collect(),
+                            }),
+                        )),
+                    ],
+                })),
+            })
+        }),
+    };
+
+    Ok(ModelDecl {
+        module: Module {
+            span: Default::default(),
+            body: imports.into_iter().chain([
+                ModuleItem::ModuleDecl(
+                    swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
+                        span: Default::default(),
+                        decl: Decl::TsInterface(Box::new(interface_decl)),
+                    }),
+                ),
+                ModuleItem::ModuleDecl(
+                    swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
+                        span: Default::default(),
+                        decl: Decl::TsTypeAlias(Box::new(primary_key_decl)),
+                    }),
+                ),
+                ModuleItem::ModuleDecl(
+                    swc_ecmascript::ast::ModuleDecl::ExportDecl(ExportDecl {
+                        span: Default::default(),
+                        decl: Decl::TsTypeAlias(Box::new(json_decl)),
+                    }),
+                ),
+            ]).collect(),
             shebang: None,
         },
-        source_map: cm,
+        docs,
+        source_map: model.source_map,
+        diagnostics: [model.diagnostics, collector.diagnostics()].concat(),
     })
 }
 
-pub fn emit_with_source_map(decl: ModelDecl, output_dts_path: &PathBuf) -> EmittedCode {
+pub fn emit_with_source_map(decl: ModelDecl, output_dts_path: &Path) -> EmittedCode {
     let mut buf = Vec::new();
     let mut src_map_buf = Vec::new();
 
     {
-        let writer = JsWriter::new(decl.source_map.clone(), "\n", &mut buf, Some(&mut src_map_buf));
+        let writer = DocWriter::new(JsWriter::new(decl.source_map.clone(), "\n", &mut buf, Some(&mut src_map_buf)));
         let mut emitter = Emitter {
             cfg: Config::default().with_minify(false),
             cm: decl.source_map.clone(),
-            comments: None,
+            comments: Some(decl.docs.comments()),
             wr: writer,
         };
 
@@ -179,7 +811,8 @@ pub fn emit_with_source_map(decl: ModelDecl, output_dts_path: &PathBuf) -> Emitt
     let code = String::from_utf8(buf).expect("utf8");
     
     // SWC builds the sourcemap internally based on the spans you provided
-    let mut sourcemap = decl.source_map.build_source_map(&src_map_buf, None, DefaultSourceMapGenConfig {});
+    let output_dir = output_dts_path.parent().unwrap_or(Path::new(""));
+    let mut sourcemap = decl.source_map.build_source_map(&src_map_buf, None, RelativeSources { output_dir });
     let mut map_buf = Vec::new();
     let dts_filename = output_dts_path
         .file_name()
@@ -191,5 +824,6 @@ pub fn emit_with_source_map(decl: ModelDecl, output_dts_path: &PathBuf) -> Emitt
     EmittedCode {
         code,
         source_map: map_json,
+        diagnostics: decl.diagnostics,
     }
 }
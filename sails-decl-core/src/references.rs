@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use swc_ecmascript::ast::{
+    Ident, ImportDecl, ImportNamedSpecifier, ImportPhase, ImportSpecifier, ModuleDecl, ModuleExportName,
+    ModuleItem, Str, TsEntityName, TsImportType, TsType, TsTypeRef,
+};
+use swc_ecmascript::visit::{VisitMut, VisitMutWith};
+
+/// Where a type that hints may refer to is declared.
+#[derive(Clone, Debug)]
+pub enum ImportSource {
+    /// A bare specifier such as `my-package/types`, emitted as is.
+    Package(String),
+    /// A file, without extension, emitted relative to the generated declaration.
+    Path(PathBuf),
+}
+
+/// A type that `$SD-type-hint` values may refer to by name.
+#[derive(Clone, Debug)]
+pub struct TypeImport {
+    /// The name used in hints.
+    pub name: String,
+    /// The exported name in `source`, when it differs from `name`.
+    pub imported: Option<String>,
+    pub source: ImportSource,
+}
+
+impl TypeImport {
+    /// Parses a `Name=module` pair. Relative modules are resolved against `base`.
+    pub fn parse(spec: &str, base: &Path) -> Option<Self> {
+        let (name, module) = spec.split_once('=')?;
+        let (name, module) = (name.trim(), module.trim());
+        if name.is_empty() || module.is_empty() {
+            return None;
+        }
+
+        let source = if module.starts_with('.') || Path::new(module).is_absolute() {
+            ImportSource::Path(base.join(module))
+        } else {
+            ImportSource::Package(module.to_string())
+        };
+
+        Some(TypeImport {
+            name: name.to_string(),
+            imported: None,
+            source,
+        })
+    }
+}
+
+/// Computes the path of `to` relative to the directory `from`, using `/`
+/// separators and a leading `./` as TypeScript module specifiers expect.
+pub fn relative_module_path(from: &Path, to: &Path) -> String {
+    let (from, to) = (normalize(from), normalize(to));
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().into_owned()));
+
+    let joined = parts.join("/");
+    if joined.starts_with("..") {
+        joined
+    } else {
+        format!("./{}", joined)
+    }
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Rewrites the types parsed from hints so they resolve from the generated
+/// declaration at `output_path`, and returns the imports they need.
+///
+/// Each type is paired with the directory of the file its hint was written in,
+/// which relative `import('...')` types are re-based from. Type references whose
+/// name matches one of `available` are imported from its declaration.
+pub fn resolve_type_references(
+    types: Vec<(&mut TsType, Option<&Path>)>,
+    output_path: &Path,
+    available: &[TypeImport],
+) -> Vec<ModuleItem> {
+    let output_dir = output_path.parent().unwrap_or(Path::new(""));
+
+    let mut collector = ReferenceCollector {
+        source_dir: None,
+        output_dir,
+        names: vec![],
+    };
+    for (ts_type, source_dir) in types {
+        collector.source_dir = source_dir;
+        ts_type.visit_mut_with(&mut collector);
+    }
+
+    // Group by module so each one gets a single import, in a stable order
+    let mut imports: BTreeMap<String, Vec<(String, Option<String>)>> = BTreeMap::new();
+    for name in collector.names {
+        let Some(import) = available.iter().find(|import| import.name == name) else {
+            continue;
+        };
+        let module = match &import.source {
+            ImportSource::Package(package) => package.clone(),
+            ImportSource::Path(path) => relative_module_path(output_dir, path),
+        };
+        let specifiers = imports.entry(module).or_default();
+        if !specifiers.iter().any(|(local, _)| *local == import.name) {
+            specifiers.push((import.name.clone(), import.imported.clone()));
+        }
+    }
+
+    imports
+        .into_iter()
+        .map(|(module, specifiers)| {
+            ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
+                span: Default::default(),
+                specifiers: specifiers
+                    .into_iter()
+                    .map(|(local, imported)| {
+                        ImportSpecifier::Named(ImportNamedSpecifier {
+                            span: Default::default(),
+                            local: ident(&local),
+                            imported: imported.map(|imported| ModuleExportName::Ident(ident(&imported))),
+                            is_type_only: false,
+                        })
+                    })
+                    .collect(),
+                src: Box::new(Str {
+                    span: Default::default(),
+                    value: module.into(),
+                    raw: None,
+                }),
+                type_only: true,
+                with: None,
+                phase: ImportPhase::Evaluation,
+            }))
+        })
+        .collect()
+}
+
+fn ident(sym: &str) -> Ident {
+    Ident {
+        span: Default::default(),
+        ctxt: Default::default(),
+        sym: sym.into(),
+        optional: false,
+    }
+}
+
+struct ReferenceCollector<'a> {
+    source_dir: Option<&'a Path>,
+    output_dir: &'a Path,
+    names: Vec<String>,
+}
+
+impl VisitMut for ReferenceCollector<'_> {
+    fn visit_mut_ts_type_ref(&mut self, type_ref: &mut TsTypeRef) {
+        let mut entity = &type_ref.type_name;
+        while let TsEntityName::TsQualifiedName(qualified) = entity {
+            entity = &qualified.left;
+        }
+        if let TsEntityName::Ident(root) = entity {
+            self.names.push(root.sym.to_string());
+        }
+
+        type_ref.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_ts_import_type(&mut self, import_type: &mut TsImportType) {
+        if let (Some(source_dir), Some(specifier)) = (self.source_dir, import_type.arg.value.as_str())
+            && specifier.starts_with('.')
+        {
+            let rebased = relative_module_path(self.output_dir, &source_dir.join(specifier));
+            import_type.arg = Str {
+                span: import_type.arg.span,
+                value: rebased.into(),
+                raw: None,
+            };
+        }
+
+        import_type.visit_mut_children_with(self);
+    }
+}
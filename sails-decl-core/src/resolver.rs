@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use swc_common::comments::SingleThreadedComments;
+use swc_common::errors::Handler;
+use swc_common::sync::Lrc;
+use swc_common::{FileName, SourceMap};
+use swc_ecmascript::ast::{Callee, Decl, Expr, KeyValueProp, Lit, MemberProp, ObjectLit, Pat, Prop, PropName, PropOrSpread, Script, Stmt, Str, VarDeclKind};
+
+use crate::references::normalize;
+use crate::source::parse_source_file;
+use crate::util::{find_module_exports_expr, get_prop_as_str};
+
+/// How many `require()`s, spreads and bindings may be followed from one
+/// expression, which also keeps circular requires from looping forever.
+const MAX_DEPTH: usize = 16;
+
+const REQUIRE_EXTENSIONS: &[&str] = &[
+    "", ".js", ".cjs", ".mjs", ".ts", ".cts", ".mts", "/index.js", "/index.ts",
+];
+
+/// The file an expression was found in, for resolving its bindings and requires.
+#[derive(Clone, Copy)]
+pub struct Scope<'a> {
+    pub script: &'a Script,
+    pub path: Option<&'a Path>,
+}
+
+/// Follows spreads, `Object.assign(...)`, `Object.freeze(...)`, relative
+/// `require()` calls, member accesses and identifiers bound by top-level
+/// declarations to fold an expression into a constant, flattening objects into
+/// a single object literal. Required files are parsed into the same `SourceMap`
+/// (and comments) as the file that requires them, so their spans stay usable.
+pub struct ModuleResolver<'a> {
+    cm: Lrc<SourceMap>,
+    comments: &'a SingleThreadedComments,
+    handler: &'a Handler,
+    modules: HashMap<PathBuf, Option<Rc<Script>>>,
+}
+
+impl<'a> ModuleResolver<'a> {
+    pub fn new(cm: Lrc<SourceMap>, comments: &'a SingleThreadedComments, handler: &'a Handler) -> Self {
+        ModuleResolver {
+            cm,
+            comments,
+            handler,
+            modules: HashMap::new(),
+        }
+    }
+
+    pub fn resolve_object(&mut self, expr: &Expr, scope: Scope) -> Option<ObjectLit> {
+        self.resolve(expr, scope, 0)
+    }
+
+    /// Every file a `require()` tried to load so far, including the ones that
+    /// do not exist, sorted.
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        let mut dependencies: Vec<PathBuf> = self.modules.keys().cloned().collect();
+        dependencies.sort();
+        dependencies
+    }
+
+    fn resolve(&mut self, expr: &Expr, scope: Scope, depth: usize) -> Option<ObjectLit> {
+        match self.fold(expr, scope, depth)? {
+            Expr::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    /// Evaluates `expr` to a constant: a literal, or an array or object literal
+    /// whose values are folded as far as they can be. Returns `None` for
+    /// anything that isn't known until runtime.
+    fn fold(&mut self, expr: &Expr, scope: Scope, depth: usize) -> Option<Expr> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        match expr {
+            Expr::Lit(_) => Some(expr.clone()),
+            // Written as a string so attribute readers only need to handle one kind
+            Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+                let quasi = tpl.quasis.first()?;
+                Some(Expr::Lit(Lit::Str(Str {
+                    span: tpl.span,
+                    value: quasi.cooked.clone()?,
+                    raw: Some(format!("`{}`", quasi.raw).into()),
+                })))
+            }
+            Expr::Unary(unary) if matches!(&*unary.arg, Expr::Lit(Lit::Num(_))) => Some(expr.clone()),
+            Expr::Paren(paren) => self.fold(&paren.expr, scope, depth),
+            // Type assertions don't change the value
+            Expr::TsAs(ts_as) => self.fold(&ts_as.expr, scope, depth),
+            Expr::TsSatisfies(satisfies) => self.fold(&satisfies.expr, scope, depth),
+            Expr::TsConstAssertion(assertion) => self.fold(&assertion.expr, scope, depth),
+            Expr::TsNonNull(non_null) => self.fold(&non_null.expr, scope, depth),
+            Expr::TsTypeAssertion(assertion) => self.fold(&assertion.expr, scope, depth),
+            Expr::Array(array) => {
+                let mut folded = array.clone();
+                for elem in folded.elems.iter_mut().flatten() {
+                    if elem.spread.is_none()
+                        && let Some(value) = self.fold(&elem.expr, scope, depth + 1)
+                    {
+                        *elem.expr = value;
+                    }
+                }
+                Some(Expr::Array(folded))
+            }
+            Expr::Object(obj) => {
+                let mut flattened = ObjectLit {
+                    span: obj.span,
+                    props: vec![],
+                };
+                for prop in &obj.props {
+                    match prop {
+                        PropOrSpread::Spread(spread) => {
+                            // Spreads that can't be resolved are dropped, like any other unknown value
+                            if let Some(spread_obj) = self.resolve(&spread.expr, scope, depth + 1) {
+                                for prop in spread_obj.props {
+                                    insert_prop(&mut flattened, prop);
+                                }
+                            }
+                        }
+                        PropOrSpread::Prop(prop) => {
+                            let prop = self.fold_prop_value(prop, scope, depth + 1);
+                            insert_prop(&mut flattened, PropOrSpread::Prop(Box::new(prop)));
+                        }
+                    }
+                }
+                Some(Expr::Object(flattened))
+            }
+            Expr::Ident(ident) => {
+                let init = find_binding(scope.script, ident.sym.as_str())?;
+                self.fold(init, scope, depth + 1)
+            }
+            Expr::Member(member) => {
+                let name = match &member.prop {
+                    MemberProp::Ident(ident) => ident.sym.to_string(),
+                    MemberProp::Computed(computed) => match self.fold(&computed.expr, scope, depth + 1)? {
+                        Expr::Lit(Lit::Str(string)) => string.value.as_str()?.to_string(),
+                        Expr::Lit(Lit::Num(number)) => number.value.to_string(),
+                        _ => return None,
+                    },
+                    MemberProp::PrivateName(_) => return None,
+                };
+                // Values were already folded in their own scope
+                match self.fold(&member.obj, scope, depth + 1)? {
+                    Expr::Object(obj) => obj.props.into_iter().rev().find_map(|prop| {
+                        let key_value = prop.as_prop()?.as_key_value()?;
+                        if get_prop_as_str(&key_value.key)? != name {
+                            return None;
+                        }
+                        Some(*key_value.value.clone())
+                    }),
+                    Expr::Array(array) => {
+                        let index: usize = name.parse().ok()?;
+                        let elem = array.elems.get(index)?.as_ref()?;
+                        elem.spread.is_none().then(|| *elem.expr.clone())
+                    }
+                    _ => None,
+                }
+            }
+            Expr::Call(call) => {
+                let Callee::Expr(callee) = &call.callee else {
+                    return None;
+                };
+
+                if callee.as_ident().is_some_and(|ident| ident.sym == "require") {
+                    let specifier = call.args.first()?.expr.as_lit()?.as_str()?.value.as_str()?;
+                    return self.fold_require(specifier, scope, depth);
+                }
+
+                let member = callee.as_member()?;
+                if member.obj.as_ident()?.sym != "Object" {
+                    return None;
+                }
+                match member.prop.as_ident()?.sym.as_str() {
+                    // Freezing doesn't change the value
+                    "freeze" => self.fold(&call.args.first()?.expr, scope, depth + 1),
+                    // Object.assign(target, ...sources)
+                    "assign" => {
+                        let mut merged = ObjectLit {
+                            span: call.span,
+                            props: vec![],
+                        };
+                        for arg in &call.args {
+                            if arg.spread.is_some() {
+                                continue;
+                            }
+                            if let Some(source) = self.resolve(&arg.expr, scope, depth + 1) {
+                                for prop in source.props {
+                                    insert_prop(&mut merged, prop);
+                                }
+                            }
+                        }
+                        Some(Expr::Object(merged))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Folds a property value while still in the scope it was written in,
+    /// since the property may end up merged into an object from another file.
+    /// Shorthand properties become key-value pairs.
+    fn fold_prop_value(&mut self, prop: &Prop, scope: Scope, depth: usize) -> Prop {
+        let (key, value) = match prop {
+            Prop::KeyValue(key_value) => (key_value.key.clone(), &*key_value.value),
+            Prop::Shorthand(ident) => match find_binding(scope.script, ident.sym.as_str()) {
+                Some(_) => (PropName::Ident(ident.clone().into()), &Expr::Ident(ident.clone())),
+                None => return prop.clone(),
+            },
+            _ => return prop.clone(),
+        };
+
+        match self.fold(value, scope, depth) {
+            Some(folded) => Prop::KeyValue(KeyValueProp {
+                key,
+                value: Box::new(folded),
+            }),
+            None => prop.clone(),
+        }
+    }
+
+    fn fold_require(&mut self, specifier: &str, scope: Scope, depth: usize) -> Option<Expr> {
+        // Only relative requires point at project files
+        if !specifier.starts_with('.') {
+            return None;
+        }
+        let base = normalize(&scope.path?.parent()?.join(specifier));
+        // The candidates tried before the one found are kept as missing
+        // modules, since creating one of them changes what is required
+        let mut found = None;
+        let candidates = REQUIRE_EXTENSIONS
+            .iter()
+            .map(|ext| PathBuf::from(format!("{}{}", base.display(), ext)));
+        for candidate in candidates {
+            if candidate.is_file() {
+                found = Some(candidate);
+                break;
+            }
+            self.modules.entry(candidate).or_insert(None);
+        }
+        let path = found?;
+
+        let script = self.load(&path)?;
+        let exports = find_module_exports_expr(&script)?;
+        let scope = Scope {
+            script: &script,
+            path: Some(&path),
+        };
+        self.fold(&exports, scope, depth + 1)
+    }
+
+    fn load(&mut self, path: &Path) -> Option<Rc<Script>> {
+        if let Some(script) = self.modules.get(path) {
+            return script.clone();
+        }
+
+        let script = std::fs::read_to_string(path).ok().and_then(|code| {
+            let file = self.cm.new_source_file(FileName::Real(path.to_path_buf()).into(), code);
+            parse_source_file(&file, Some(self.comments), self.handler).map(Rc::new)
+        });
+
+        self.modules.insert(path.to_path_buf(), script.clone());
+        script
+    }
+}
+
+/// Finds the initializer of a top-level `const` binding. `let` and `var`
+/// bindings can be reassigned, so their initial value can't be trusted.
+pub fn find_binding<'s>(script: &'s Script, name: &str) -> Option<&'s Expr> {
+    script.body.iter().find_map(|stmt| {
+        let Stmt::Decl(Decl::Var(var)) = stmt else {
+            return None;
+        };
+        if var.kind != VarDeclKind::Const {
+            return None;
+        }
+        var.decls.iter().find_map(|decl| match &decl.name {
+            Pat::Ident(binding) if binding.id.sym == name => decl.init.as_deref(),
+            _ => None,
+        })
+    })
+}
+
+// Later properties override earlier ones but keep their position, as in JS.
+fn insert_prop(obj: &mut ObjectLit, prop: PropOrSpread) {
+    let key = match &prop {
+        PropOrSpread::Prop(p) => match &**p {
+            Prop::KeyValue(kv) => get_prop_as_str(&kv.key).map(str::to_string),
+            Prop::Shorthand(ident) => Some(ident.sym.to_string()),
+            _ => None,
+        },
+        PropOrSpread::Spread(_) => None,
+    };
+
+    if let Some(key) = key {
+        let existing = obj.props.iter().position(|existing| {
+            existing
+                .as_prop()
+                .and_then(|p| p.as_key_value())
+                .and_then(|kv| get_prop_as_str(&kv.key))
+                == Some(key.as_str())
+        });
+        if let Some(index) = existing {
+            obj.props[index] = prop;
+            return;
+        }
+    }
+
+    obj.props.push(prop);
+}
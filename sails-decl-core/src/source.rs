@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use swc_common::comments::Comments;
+use swc_common::errors::Handler;
+use swc_common::{DUMMY_SP, FileName, SourceFile};
+use swc_ecma_parser::{Parser, StringInput, Syntax, TsSyntax, lexer::Lexer};
+use swc_ecmascript::ast::{
+    AssignExpr, AssignOp, AssignTarget, BindingIdent, CallExpr, Callee, Decl, Expr, ExprOrSpread, ExprStmt,
+    Ident, IdentName, ImportSpecifier, MemberExpr, MemberProp, Module, ModuleDecl, ModuleExportName, ModuleItem,
+    Pat, Program, Script, SimpleAssignTarget, Stmt, VarDecl, VarDeclKind, VarDeclarator,
+};
+
+/// Extensions of the files models, helpers and the fragments they require may be written in.
+pub const SOURCE_EXTENSIONS: &[&str] = &["js", "cjs", "mjs", "ts", "cts", "mts"];
+
+/// Whether `path` is a model or helper source file. Declaration files are skipped.
+pub fn is_source_file(path: &Path) -> bool {
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    if [".d.ts", ".d.cts", ".d.mts"].iter().any(|ext| file_name.ends_with(ext)) {
+        return false;
+    }
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext))
+}
+
+/// Removes the extension from the file name `path` ends in, if it has one.
+pub fn strip_extension(path: &str) -> &str {
+    let file_name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+    match path[file_name_start..].rfind('.') {
+        Some(dot) if dot > 0 => &path[..file_name_start + dot],
+        _ => path,
+    }
+}
+
+/// Parses a source file with the syntax its extension calls for. ES modules are
+/// rewritten into the equivalent CommonJS script, so the rest of the crate only
+/// has to understand `module.exports` and `require()`. Errors are emitted to
+/// `handler`, and `None` is returned if the file could not be parsed at all.
+pub fn parse_source_file(file: &SourceFile, comments: Option<&dyn Comments>, handler: &Handler) -> Option<Script> {
+    let extension = match &*file.name {
+        FileName::Real(path) => path.extension().and_then(|ext| ext.to_str()).unwrap_or("js"),
+        _ => "js",
+    };
+
+    let syntax = match extension {
+        "ts" | "cts" | "mts" => Syntax::Typescript(TsSyntax::default()),
+        _ => Syntax::Es(Default::default()),
+    };
+
+    let lexer = Lexer::new(syntax, Default::default(), StringInput::from(file), comments);
+    let mut parser = Parser::new_from(lexer);
+
+    let program = match extension {
+        "cjs" | "cts" => parser.parse_commonjs().map(Program::Script),
+        "mjs" | "mts" => parser.parse_module().map(Program::Module),
+        // Plain `.js` and `.ts` files may be either
+        _ => parser.parse_program(),
+    };
+
+    for e in parser.take_errors() {
+        e.into_diagnostic(handler).emit();
+    }
+
+    match program.map_err(|e| e.into_diagnostic(handler).emit()).ok()? {
+        Program::Script(script) => Some(script),
+        Program::Module(module) => Some(module_to_script(module)),
+    }
+}
+
+// `export default x` becomes `module.exports = x`, exported bindings are
+// assigned to `exports`, and imports become `require()`s of the same module.
+fn module_to_script(module: Module) -> Script {
+    let mut body = vec![];
+    let mut exported = vec![];
+
+    for item in module.body {
+        let decl = match item {
+            ModuleItem::Stmt(stmt) => {
+                body.push(stmt);
+                continue;
+            }
+            ModuleItem::ModuleDecl(decl) => decl,
+        };
+
+        match decl {
+            ModuleDecl::ExportDefaultExpr(export) => {
+                body.push(assign_stmt(member(ident("module"), "exports"), export.expr));
+            }
+            ModuleDecl::ExportDecl(export) => {
+                if let Decl::Var(var) = &export.decl {
+                    exported.extend(var.decls.iter().filter_map(|decl| decl.name.as_ident()).map(|binding| binding.id.sym.to_string()));
+                }
+                body.push(Stmt::Decl(export.decl));
+            }
+            ModuleDecl::Import(import) if !import.type_only => {
+                for specifier in import.specifiers {
+                    let require = Box::new(Expr::Call(CallExpr {
+                        callee: Callee::Expr(Box::new(ident("require"))),
+                        args: vec![ExprOrSpread {
+                            spread: None,
+                            expr: Box::new(Expr::Lit((*import.src).clone().into())),
+                        }],
+                        ..Default::default()
+                    }));
+                    let (local, init) = match specifier {
+                        ImportSpecifier::Named(named) if !named.is_type_only => {
+                            let imported = match &named.imported {
+                                Some(ModuleExportName::Ident(imported)) => imported.sym.to_string(),
+                                Some(ModuleExportName::Str(imported)) => match imported.value.as_str() {
+                                    Some(imported) => imported.to_string(),
+                                    None => continue,
+                                },
+                                None => named.local.sym.to_string(),
+                            };
+                            (named.local, Box::new(Expr::Member(member(*require, &imported))))
+                        }
+                        ImportSpecifier::Named(_) => continue,
+                        ImportSpecifier::Default(default) => (default.local, require),
+                        ImportSpecifier::Namespace(namespace) => (namespace.local, require),
+                    };
+                    body.push(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+                        kind: VarDeclKind::Const,
+                        decls: vec![VarDeclarator {
+                            span: DUMMY_SP,
+                            name: Pat::Ident(BindingIdent { id: local, type_ann: None }),
+                            init: Some(init),
+                            definite: false,
+                        }],
+                        ..Default::default()
+                    }))));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for name in exported {
+        body.push(assign_stmt(member(ident("exports"), &name), Box::new(ident(&name))));
+    }
+
+    Script {
+        span: module.span,
+        body,
+        shebang: module.shebang,
+    }
+}
+
+fn ident(sym: &str) -> Expr {
+    Expr::Ident(Ident::new_no_ctxt(sym.into(), DUMMY_SP))
+}
+
+fn member(obj: Expr, prop: &str) -> MemberExpr {
+    MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(obj),
+        prop: MemberProp::Ident(IdentName::new(prop.into(), DUMMY_SP)),
+    }
+}
+
+fn assign_stmt(target: MemberExpr, value: Box<Expr>) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::Assign,
+            left: AssignTarget::Simple(SimpleAssignTarget::Member(target)),
+            right: value,
+        })),
+    })
+}
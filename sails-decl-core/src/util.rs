@@ -1,13 +1,24 @@
 extern crate swc_common;
 extern crate swc_ecma_parser;
+use std::borrow::Cow;
+use std::path::Path;
+
+use swc_common::comments::{CommentKind, Comments, SingleThreadedComments};
+use swc_common::errors::{DiagnosticId, Handler};
+use swc_common::source_map::SourceMapGenConfig;
 use swc_common::sync::Lrc;
-use swc_common::{FileName, SourceMap};
+use swc_common::{BytePos, FileName, SourceMap, Span, Spanned};
 use swc_ecma_parser::{Parser, StringInput, Syntax, lexer::Lexer};
 use swc_ecmascript::ast::TsType::{self};
 use swc_ecmascript::ast::{
-    ObjectLit, PropName, Script, TsKeywordType, TsKeywordTypeKind, TsUnionOrIntersectionType, TsUnionType
+    Decl, Expr, Ident, IdentName, KeyValueProp, Lit, Prop, PropOrSpread, SpreadElement, Stmt, ObjectLit, PropName, Script, Str, TsArrayType, TsEntityName, TsKeywordType,
+    TsKeywordTypeKind, TsLit, TsLitType, TsTypeRef, TsUnionOrIntersectionType, TsUnionType
 };
 
+use crate::config::{Nullability, SailsVersion, TypeMapping};
+use crate::diagnostics::{Diagnostic, codes};
+use crate::references::relative_module_path;
+
 pub fn get_prop_as_str(prop: &PropName) -> Option<&str> {
     match prop {
         PropName::Ident(ident) => Some(ident.sym.as_str()),
@@ -16,8 +27,54 @@ pub fn get_prop_as_str(prop: &PropName) -> Option<&str> {
     }
 }
 
-pub fn parse_type_hint(type_hint: &str) -> Result<TsType, ()> {
-    let temp_type = format!("0 as {}", type_hint);
+pub fn get_str_prop<'a>(obj: &'a ObjectLit, name: &str) -> Option<&'a str> {
+    obj.props.iter().find_map(|prop| {
+        let key_value = prop.as_prop()?.as_key_value()?;
+        if get_prop_as_str(&key_value.key)? != name {
+            return None;
+        }
+        key_value.value.as_lit()?.as_str()?.value.as_str()
+    })
+}
+
+pub fn get_bool_prop(obj: &ObjectLit, name: &str) -> Option<bool> {
+    obj.props.iter().find_map(|prop| {
+        let key_value = prop.as_prop()?.as_key_value()?;
+        if get_prop_as_str(&key_value.key)? != name {
+            return None;
+        }
+        Some(key_value.value.as_lit()?.as_bool()?.value)
+    })
+}
+
+pub fn get_object_prop<'a>(obj: &'a ObjectLit, name: &str) -> Option<&'a ObjectLit> {
+    obj.props.iter().find_map(|prop| {
+        let key_value = prop.as_prop()?.as_key_value()?;
+        if get_prop_as_str(&key_value.key)? != name {
+            return None;
+        }
+        key_value.value.as_object()
+    })
+}
+
+pub fn keyword_type(kind: TsKeywordTypeKind) -> TsType {
+    TsType::TsKeywordType(TsKeywordType {
+        span: Default::default(),
+        kind,
+    })
+}
+
+const TYPE_HINT_PREFIX: &str = "type __SDTypeHint = ";
+
+pub struct TypeHintError {
+    pub message: String,
+    /// Where the error is. [`parse_type_hint`] reports it relative to the start
+    /// of the hint; [`ts_type_from_attribute`] maps it into the model source.
+    pub span: Span,
+}
+
+pub fn parse_type_hint(type_hint: &str) -> Result<TsType, TypeHintError> {
+    let temp_type = format!("{}{}", TYPE_HINT_PREFIX, type_hint);
     let cm: Lrc<SourceMap> = Default::default();
     let type_file = cm.new_source_file(FileName::Anon.into(), temp_type);
 
@@ -30,36 +87,178 @@ pub fn parse_type_hint(type_hint: &str) -> Result<TsType, ()> {
 
     let mut type_parser = Parser::new_from(type_lexer);
 
-    if let Ok(expr) = type_parser.parse_expr() {
-        if let Some(_ts_as) = expr.as_ts_as() {
-            Ok(*_ts_as.type_ann.clone())
-        } else {
-            Err(())
+    // Offsets of the parser's spans relative to the start of the hint itself
+    let hint_start = type_file.start_pos.0 + TYPE_HINT_PREFIX.len() as u32;
+    let relative_error = |error: swc_ecma_parser::error::Error| {
+        let span = error.span();
+        TypeHintError {
+            message: error.kind().msg().into_owned(),
+            span: Span::new(
+                BytePos(span.lo.0.saturating_sub(hint_start)),
+                BytePos(span.hi.0.saturating_sub(hint_start)),
+            ),
         }
-    } else {
-        Err(())
+    };
+
+    let script = type_parser.parse_script().map_err(relative_error)?;
+
+    if let Some(error) = type_parser.take_errors().into_iter().next() {
+        return Err(relative_error(error));
+    }
+
+    match script.body.as_slice() {
+        [Stmt::Decl(Decl::TsTypeAlias(alias))] => Ok(*alias.type_ann.clone()),
+        _ => Err(TypeHintError {
+            message: "Expected a single type".to_string(),
+            span: Span::new(BytePos(0), BytePos(type_hint.len() as u32)),
+        }),
     }
 }
 
+/// Finds a `/** @type {...} */` JSDoc comment in front of `pos` and returns
+/// the type expression between the braces with its position in the source.
+pub fn jsdoc_type_hint(comments: &SingleThreadedComments, pos: BytePos) -> Option<(String, Span)> {
+    comments.get_leading(pos)?.iter().rev().find_map(|comment| {
+        if comment.kind != CommentKind::Block || !comment.text.starts_with('*') {
+            return None;
+        }
+
+        let text = comment.text.as_str();
+        let tag = text.find("@type")? + "@type".len();
+        let open = tag + text[tag..].find(|c: char| !c.is_whitespace())?;
+        if !text[open..].starts_with('{') {
+            return None;
+        }
+
+        let mut depth = 0;
+        let close = open + text[open..].char_indices().find_map(|(i, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => {}
+            }
+            None
+        })?;
+
+        // `text` starts after the opening `/*`
+        let text_start = comment.span.lo + BytePos(2);
+        let hint = text[open + 1..close].trim();
+        let leading_ws = text[open + 1..close].len() - text[open + 1..close].trim_start().len();
+        let lo = text_start + BytePos((open + 1 + leading_ws) as u32);
+        Some((hint.to_string(), Span::new(lo, lo + BytePos(hint.len() as u32))))
+    })
+}
+
+/// Uses a JSDoc `@type` comment on an attribute key as its `$SD-type-hint`,
+/// unless the definition already has one.
+pub fn with_jsdoc_type_hint<'a>(
+    attribute: Cow<'a, ObjectLit>,
+    comments: &SingleThreadedComments,
+    key_pos: BytePos,
+) -> Cow<'a, ObjectLit> {
+    if get_str_prop(&attribute, "$SD-type-hint").is_some() {
+        return attribute;
+    }
+    let Some((hint, span)) = jsdoc_type_hint(comments, key_pos) else {
+        return attribute;
+    };
+
+    let mut attribute = attribute.into_owned();
+    attribute.props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key: PropName::Str(Str {
+            span: Default::default(),
+            value: "$SD-type-hint".into(),
+            raw: None,
+        }),
+        // Spanning the braces lets hint errors point into the comment
+        value: Box::new(Expr::Lit(Lit::Str(Str {
+            span: Span::new(span.lo - BytePos(1), span.hi + BytePos(1)),
+            raw: Some(format!("'{}'", hint).into()),
+            value: hint.into(),
+        }))),
+    }))));
+    Cow::Owned(attribute)
+}
+
 pub struct AttributeTypeInfo {
   pub ts_type: TsType,
   pub required: bool,
 }
 
-pub fn ts_type_from_attribute(attribute: &ObjectLit) -> Option<AttributeTypeInfo> {
+pub fn type_ref(name: &str) -> TsType {
+    TsType::TsTypeRef(TsTypeRef {
+        span: Default::default(),
+        type_name: TsEntityName::Ident(Ident {
+            span: Default::default(),
+            ctxt: Default::default(),
+            sym: name.into(),
+            optional: false,
+        }),
+        type_params: None,
+    })
+}
+
+/// Maps the Sails 0.12 attribute types, which Sails 1.0 collapsed into
+/// `string`/`number`/`boolean`/`json`/`ref`.
+fn legacy_ts_type(attribute_type: &str) -> Option<TsType> {
+    match attribute_type {
+        "string" | "text" | "mediumtext" | "longtext" | "email" | "objectid" => {
+            Some(keyword_type(TsKeywordTypeKind::TsStringKeyword))
+        }
+        "integer" | "float" => Some(keyword_type(TsKeywordTypeKind::TsNumberKeyword)),
+        "boolean" => Some(keyword_type(TsKeywordTypeKind::TsBooleanKeyword)),
+        "date" | "datetime" => Some(type_ref("Date")),
+        "binary" => Some(type_ref("Buffer")),
+        "array" => Some(TsType::TsArrayType(TsArrayType {
+            span: Default::default(),
+            elem_type: Box::new(keyword_type(TsKeywordTypeKind::TsAnyKeyword)),
+        })),
+        "json" | "mediumjson" | "longjson" => Some(keyword_type(TsKeywordTypeKind::TsAnyKeyword)),
+        _ => None,
+    }
+}
+
+fn literal_union(values: Vec<TsLit>) -> TsType {
+    TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(TsUnionType {
+        span: Default::default(),
+        types: values
+            .into_iter()
+            .map(|lit| {
+                Box::new(TsType::TsLitType(TsLitType {
+                    span: Default::default(),
+                    lit,
+                }))
+            })
+            .collect(),
+    }))
+}
+
+/// Resolves the TypeScript type of an attribute (or helper input) definition.
+/// Returns `Ok(None)` when the type can't be determined and an error when an
+/// explicit `$SD-type-hint` is not a valid TypeScript type.
+pub fn ts_type_from_attribute(
+    attribute: &ObjectLit,
+    sails_version: SailsVersion,
+    types: &TypeMapping,
+) -> Result<Option<AttributeTypeInfo>, TypeHintError> {
     let mut attribute_type: Option<&str> = None;
+    let mut enum_values: Option<Vec<TsLit>> = None;
     let mut attribute_type_hint: Option<&str> = None;
+    let mut attribute_type_hint_lit: Option<&Str> = None;
     let mut attribute_required: bool = false;
     let mut allows_null: bool = false;
 
     for _attr_field in &attribute.props {
-        let _attr_field_prop = match _attr_field.as_prop() {
-            Some(prop) => prop,
-            None => continue,
+        // Shorthand, method, getter and setter properties can't be interpreted
+        let Some(attribute_pair) = _attr_field.as_prop().and_then(|prop| prop.as_key_value()) else {
+            continue;
         };
 
-        let attribute_pair = _attr_field_prop.as_key_value().unwrap();
-
         let attribute_name = match get_prop_as_str(&attribute_pair.key) {
             Some(name) => name,
             None => continue,
@@ -68,35 +267,41 @@ pub fn ts_type_from_attribute(attribute: &ObjectLit) -> Option<AttributeTypeInfo
 
         match attribute_name {
             "type" => {
-                if attribute_value.is_lit() {
-                    let _lit = attribute_value.as_lit().unwrap();
-                    if _lit.is_str() {
-                        attribute_type = _lit.as_str().unwrap().value.as_str();
-                    }
+                if let Some(Lit::Str(string)) = attribute_value.as_lit() {
+                    attribute_type = string.value.as_str();
                 }
             }
             "required" => {
-                if attribute_value.is_lit() {
-                    let _lit = attribute_value.as_lit().unwrap();
-                    if _lit.is_bool() {
-                        attribute_required = _lit.as_bool().unwrap().value;
-                    }
+                if let Some(Lit::Bool(boolean)) = attribute_value.as_lit() {
+                    attribute_required = boolean.value;
                 }
             }
             "allowNull" => {
-                if attribute_value.is_lit() {
-                    let _lit = attribute_value.as_lit().unwrap();
-                    if _lit.is_bool() {
-                        allows_null = _lit.as_bool().unwrap().value;
-                    }
+                if let Some(Lit::Bool(boolean)) = attribute_value.as_lit() {
+                    allows_null = boolean.value;
+                }
+            }
+            "enum" if sails_version == SailsVersion::Legacy => {
+                if let Some(array) = attribute_value.as_array() {
+                    enum_values = array
+                        .elems
+                        .iter()
+                        .map(|elem| match elem.as_ref()?.expr.as_lit()? {
+                            Lit::Str(string) => Some(TsLit::Str(Str {
+                                span: Default::default(),
+                                value: string.value.clone(),
+                                raw: None,
+                            })),
+                            Lit::Num(number) => Some(TsLit::Number(number.clone())),
+                            _ => None,
+                        })
+                        .collect();
                 }
             }
             "$SD-type-hint" => {
-                if attribute_value.is_lit() {
-                    let _lit = attribute_value.as_lit().unwrap();
-                    if _lit.is_str() {
-                        attribute_type_hint = _lit.as_str().unwrap().value.as_str();
-                    }
+                if let Some(Lit::Str(string)) = attribute_value.as_lit() {
+                    attribute_type_hint = string.value.as_str();
+                    attribute_type_hint_lit = Some(string);
                 }
             }
             _ => {
@@ -105,7 +310,30 @@ pub fn ts_type_from_attribute(attribute: &ObjectLit) -> Option<AttributeTypeInfo
         }
     }
 
-    match attribute_type_hint.or(attribute_type) {
+    // Hints are written by hand, so only the `type` itself is overridden
+    let overridden = attribute_type
+        .filter(|_| attribute_type_hint.is_none())
+        .and_then(|name| types.overrides.get(name))
+        .cloned();
+
+    let legacy_type = match (sails_version, attribute_type_hint) {
+        (SailsVersion::Legacy, None) => enum_values
+            .filter(|values| !values.is_empty())
+            .map(literal_union)
+            .or_else(|| overridden.clone())
+            .or_else(|| attribute_type.and_then(legacy_ts_type)),
+        _ => None,
+    };
+
+    let nullable = match types.nullability {
+        Nullability::Attribute => allows_null,
+        Nullability::Always => true,
+        Nullability::Never => false,
+    };
+
+    let resolved = match attribute_type_hint.or(attribute_type) {
+        _ if legacy_type.is_some() => legacy_type,
+        _ if overridden.is_some() => overridden,
         Some("string") => Some(TsType::TsKeywordType(TsKeywordType {
             span: Default::default(),
             kind: TsKeywordTypeKind::TsStringKeyword,
@@ -126,11 +354,18 @@ pub fn ts_type_from_attribute(attribute: &ObjectLit) -> Option<AttributeTypeInfo
             span: Default::default(),
             kind: TsKeywordTypeKind::TsAnyKeyword,
         })),
+        Some(x) if attribute_type_hint.is_some() => Some(parse_type_hint(x).map_err(|mut error| {
+            if let Some(hint_lit) = attribute_type_hint_lit {
+                error.span = hint_error_span(hint_lit, error.span);
+            }
+            error
+        })?),
         Some(x) => parse_type_hint(x).ok(),
         None => None,
-    }
-    .map(|hint| AttributeTypeInfo {
-        ts_type: if allows_null {
+    };
+
+    Ok(resolved.map(|hint| AttributeTypeInfo {
+        ts_type: if !nullable {
           hint
         } else {
           TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(TsUnionType {
@@ -145,18 +380,143 @@ pub fn ts_type_from_attribute(attribute: &ObjectLit) -> Option<AttributeTypeInfo
           }))
         },
         required: attribute_required,
+    }))
+}
+
+/// Reports an invalid type hint. Unless `strict`, the attribute is only skipped,
+/// so this is a warning rather than an error.
+pub fn emit_type_hint_error(handler: &Handler, error: &TypeHintError, strict: bool) {
+    let message = format!("invalid `$SD-type-hint`: {}", error.message);
+    // Attributes inherited from `config/models.js` have no position in the model's source
+    let inherited = error.span.is_dummy();
+    let mut diagnostic = match (strict, inherited) {
+        (true, true) => handler.struct_err(&message),
+        (true, false) => handler.struct_span_err(error.span, &message),
+        (false, true) => handler.struct_warn(&message),
+        (false, false) => handler.struct_span_warn(error.span, &message),
+    };
+    diagnostic.code(DiagnosticId::Error(codes::INVALID_TYPE_HINT.into()));
+    if inherited {
+        diagnostic.note("in a default attribute from config/models.js");
+    }
+    diagnostic.emit();
+}
+
+// Maps a span relative to the hint's value onto the string literal it was read
+// from. This is only exact when the literal has no escapes; otherwise the
+// whole literal is blamed.
+fn hint_error_span(hint: &Str, relative: Span) -> Span {
+    let verbatim = hint
+        .raw
+        .as_ref()
+        .is_some_and(|raw| raw.len() == hint.value.len() + 2);
+
+    if !verbatim || hint.span.is_dummy() {
+        return hint.span;
+    }
+
+    let start = hint.span.lo + BytePos(1);
+    let hi = (start + relative.hi).min(hint.span.hi - BytePos(1));
+    let lo = (start + relative.lo).min(hi);
+    Span::new(lo, if hi > lo { hi } else { lo + BytePos(1) })
+}
+
+/// Builds the value of `module.exports` from the top-level statements that set
+/// it, in order: assignments to `module.exports` itself, property assignments
+/// on `module.exports` or `exports`, and `Object.assign(module.exports, ...)`.
+/// Spreads and identifiers in the result are left for the resolver to follow.
+pub fn find_module_exports_expr(module: &Script) -> Option<Cow<'_, Expr>> {
+    let mut base: Option<&Expr> = None;
+    let mut additions: Vec<PropOrSpread> = vec![];
+    // `exports` stops aliasing `module.exports` once the latter is reassigned
+    let mut exports_detached = false;
+
+    for item in &module.body {
+        let Some(expr) = item.as_expr().map(|stmt| &*stmt.expr) else {
+            continue;
+        };
+
+        if let Some(assign) = expr.as_assign() {
+            let Some(member) = assign.left.as_simple().and_then(|target| target.as_member()) else {
+                continue;
+            };
+            let Some(name) = member.prop.as_ident().map(|ident| ident.sym.as_str()) else {
+                continue;
+            };
+
+            if is_module_exports(&member.obj) || (!exports_detached && is_ident(&member.obj, "exports")) {
+                additions.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                    key: PropName::Ident(IdentName::new(name.into(), member.prop.span())),
+                    value: assign.right.clone(),
+                }))));
+            } else if is_ident(&member.obj, "module") && name == "exports" {
+                base = Some(&assign.right);
+                additions.clear();
+                exports_detached = true;
+            }
+        } else if let Some(call) = expr.as_call()
+            && let Some(callee) = call.callee.as_expr().and_then(|callee| callee.as_member())
+            && is_ident(&callee.obj, "Object")
+            && callee.prop.as_ident().is_some_and(|prop| prop.sym == "assign")
+            && call.args.first().is_some_and(|target| is_module_exports(&target.expr))
+        {
+            additions.extend(call.args.iter().skip(1).filter(|arg| arg.spread.is_none()).map(|arg| {
+                PropOrSpread::Spread(SpreadElement {
+                    dot3_token: arg.expr.span(),
+                    expr: arg.expr.clone(),
+                })
+            }));
+        }
+    }
+
+    match (base, additions.is_empty()) {
+        (Some(base), true) => Some(Cow::Borrowed(base)),
+        (None, true) => None,
+        (base, false) => {
+            let mut props: Vec<PropOrSpread> = base
+                .map(|base| {
+                    PropOrSpread::Spread(SpreadElement {
+                        dot3_token: base.span(),
+                        expr: Box::new(base.clone()),
+                    })
+                })
+                .into_iter()
+                .collect();
+            props.extend(additions);
+            Some(Cow::Owned(Expr::Object(ObjectLit {
+                span: module.span,
+                props,
+            })))
+        }
+    }
+}
+
+fn is_ident(expr: &Expr, name: &str) -> bool {
+    expr.as_ident().is_some_and(|ident| ident.sym == name)
+}
+
+fn is_module_exports(expr: &Expr) -> bool {
+    expr.as_member().is_some_and(|member| {
+        is_ident(&member.obj, "module") && member.prop.as_ident().is_some_and(|prop| prop.sym == "exports")
     })
 }
 
-pub fn find_module_exports(module: Script) -> Option<ObjectLit> {
-    module
-    .body
-    .iter()
-    .find_map(|item| {
+// Matches both `module.exports.<name> = {...}` (the style used by files in
+// `config/`) and `module.exports = { <name>: {...} }`.
+pub fn find_module_exports_member(module: Script, name: &str) -> Option<ObjectLit> {
+    module.body.iter().find_map(|item| {
         let assign = item.as_expr()?.expr.as_assign()?;
         let member = assign.left.as_simple()?.as_member()?;
 
-        if member.obj.as_ident()?.sym != "module" || member.prop.as_ident()?.sym != "exports" {
+        if member.prop.as_ident()?.sym != name {
+            if member.obj.as_ident()?.sym != "module" || member.prop.as_ident()?.sym != "exports" {
+                return None;
+            }
+            return get_object_prop(assign.right.as_object()?, name).cloned();
+        }
+
+        let exports = member.obj.as_member()?;
+        if exports.obj.as_ident()?.sym != "module" || exports.prop.as_ident()?.sym != "exports" {
             return None;
         }
 
@@ -164,7 +524,25 @@ pub fn find_module_exports(module: Script) -> Option<ObjectLit> {
     })
 }
 
+/// Lists the `sources` of a declaration's source map relative to the
+/// directory the declaration is written to, so the map stays valid when the
+/// project is checked out somewhere else.
+pub struct RelativeSources<'a> {
+    pub output_dir: &'a Path,
+}
+
+impl SourceMapGenConfig for RelativeSources<'_> {
+    fn file_name_to_source(&self, f: &FileName) -> String {
+        match f {
+            FileName::Real(path) => relative_module_path(self.output_dir, path),
+            _ => f.to_string(),
+        }
+    }
+}
+
 pub struct EmittedCode {
     pub code: String,
     pub source_map: String,
+    /// Problems found in the sources that still made it into the output.
+    pub diagnostics: Vec<Diagnostic>,
 }
\ No newline at end of file
@@ -0,0 +1,445 @@
+//! Checks the declarations generated for small models, end to end.
+
+use std::path::PathBuf;
+
+use sails_decl_core::config::{Nullability, ProjectConfig, SailsVersion};
+use sails_decl_core::model::{ModelIndex, emit_with_source_map, gen_decl, parse_model};
+use sails_decl_core::references::TypeImport;
+
+/// Declares a model named `Pet` from `code`, as if it was in `api/models`.
+fn declare(code: &str, project: &ProjectConfig) -> String {
+    declare_file("Pet.js", code, project)
+}
+
+/// Declares `code` as the model `Pet`, read from `api/models/<file_name>`.
+fn declare_file(file_name: &str, code: &str, project: &ProjectConfig) -> String {
+    let path = PathBuf::from("/app/api/models").join(file_name);
+    let declaration_path = PathBuf::from("/app/types/Pet.d.ts");
+    let model = parse_model(code.to_string(), "Pet".to_string(), Some(path)).unwrap();
+    let mut index = ModelIndex::default();
+    index.insert(&model, project, declaration_path.clone());
+    let decl = gen_decl(model, project, &index, &declaration_path).unwrap();
+    emit_with_source_map(decl, &declaration_path).code
+}
+
+fn with_nullability(nullability: Nullability) -> ProjectConfig {
+    let mut project = ProjectConfig::default();
+    project.types.nullability = nullability;
+    project
+}
+
+/// Loads the project settings from the given `config/` files.
+fn load_project(name: &str, config_files: &[(&str, &str)]) -> ProjectConfig {
+    let root = std::env::temp_dir().join(format!("sails-decl-declarations-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(root.join("config")).unwrap();
+    for (file, contents) in config_files {
+        std::fs::write(root.join("config").join(file), contents).unwrap();
+    }
+    let project = ProjectConfig::load(&root);
+    std::fs::remove_dir_all(&root).unwrap();
+    project
+}
+
+const NULLABLE_MODEL: &str = "module.exports = {
+  attributes: {
+    name: { type: 'string', required: true },
+    nickname: { type: 'string', allowNull: true },
+  },
+};";
+
+#[test]
+fn attribute_nullability_follows_allow_null() {
+    let code = declare(NULLABLE_MODEL, &with_nullability(Nullability::Attribute));
+    assert!(code.contains(r#"["name"]: string;"#), "{}", code);
+    assert!(code.contains(r#"["nickname"]?: string | null;"#), "{}", code);
+}
+
+#[test]
+fn always_nullability_allows_null_everywhere() {
+    let code = declare(NULLABLE_MODEL, &with_nullability(Nullability::Always));
+    assert!(code.contains(r#"["name"]: string | null;"#), "{}", code);
+    assert!(code.contains(r#"["nickname"]?: string | null;"#), "{}", code);
+}
+
+#[test]
+fn never_nullability_allows_null_nowhere() {
+    let code = declare(NULLABLE_MODEL, &with_nullability(Nullability::Never));
+    assert!(code.contains(r#"["name"]: string;"#), "{}", code);
+    assert!(code.contains(r#"["nickname"]?: string;"#), "{}", code);
+}
+
+#[test]
+fn schemaless_json_keeps_declared_attributes() {
+    let code = declare(
+        "module.exports = {
+  schema: false,
+  attributes: {
+    name: { type: 'string', required: true },
+    password: { type: 'string', protect: true },
+  },
+};",
+        &ProjectConfig::default(),
+    );
+    let json = code.lines().skip_while(|line| !line.contains("Pet__JSON")).collect::<Vec<_>>().join("\n");
+    assert!(!json.contains("Omit<"), "{}", code);
+    assert!(json.contains(r#"["name"]: string;"#), "{}", code);
+    assert!(!json.contains(r#"["password"]"#), "{}", code);
+    assert!(json.contains("[key: string]: unknown;"), "{}", code);
+}
+
+#[test]
+fn source_map_sources_are_relative_to_the_declaration() {
+    let path = PathBuf::from("/app/api/models/Pet.js");
+    let declaration_path = PathBuf::from("/app/types/models/Pet.d.ts");
+    let project = ProjectConfig::default();
+    let model = parse_model(NULLABLE_MODEL.to_string(), "Pet".to_string(), Some(path)).unwrap();
+    let decl = gen_decl(model, &project, &ModelIndex::default(), &declaration_path).unwrap();
+    let source_map = emit_with_source_map(decl, &declaration_path).source_map;
+    assert!(source_map.contains(r#""sources":["../../api/models/Pet.js"]"#), "{}", source_map);
+}
+
+#[test]
+fn only_const_bindings_are_folded() {
+    let code = declare(
+        "const size = { type: 'number' };
+let base = { type: 'string' };
+base = { type: 'number' };
+module.exports = {
+  attributes: {
+    size,
+    weight: base,
+  },
+};",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["size"]?: number;"#), "{}", code);
+    assert!(!code.contains(r#"["weight"]"#), "{}", code);
+}
+
+#[test]
+fn template_literal_types_are_read() {
+    let code = declare(
+        "module.exports = {
+  attributes: {
+    name: { type: `string`, required: true },
+  },
+};",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]: string;"#), "{}", code);
+}
+
+#[test]
+fn missing_requires_are_dependencies() {
+    let model = parse_model(
+        "module.exports = { attributes: { ...require('./shared/missing') } };".to_string(),
+        "Pet".to_string(),
+        Some(PathBuf::from("/app/api/models/Pet.js")),
+    )
+    .unwrap();
+    assert!(model.dependencies.contains(&PathBuf::from("/app/api/models/shared/missing.js")));
+    assert!(model.dependencies.contains(&PathBuf::from("/app/api/models/shared/missing/index.ts")));
+}
+
+#[test]
+fn legacy_auto_attributes_defer_to_default_attributes() {
+    let mut project = load_project(
+        "legacy-defaults",
+        &[("models.js", "module.exports.models = { attributes: { id: { type: 'string' } } };")],
+    );
+    project.sails_version = SailsVersion::Legacy;
+    let code = declare("module.exports = { attributes: { name: 'string' } };", &project);
+    assert_eq!(code.matches(r#"["id"]"#).count(), 1, "{}", code);
+    assert!(code.contains(r#"["createdAt"]: Date;"#), "{}", code);
+}
+
+#[test]
+fn unknown_association_targets_fall_back_to_any_primary_key() {
+    let code = declare(
+        "module.exports = { attributes: { owner: { model: 'person', required: true } } };",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["owner"]: string | number;"#), "{}", code);
+}
+
+#[test]
+fn documented_attributes_keep_their_own_line_and_mapping() {
+    let path = PathBuf::from("/app/api/models/Pet.js");
+    let declaration_path = PathBuf::from("/app/types/Pet.d.ts");
+    let project = ProjectConfig::default();
+    let model = parse_model(
+        "module.exports = { attributes: { role: { type: 'string', description: 'What the pet does' } } };".to_string(),
+        "Pet".to_string(),
+        Some(path),
+    )
+    .unwrap();
+    let decl = gen_decl(model, &project, &ModelIndex::default(), &declaration_path).unwrap();
+    let emitted = emit_with_source_map(decl, &declaration_path);
+    assert!(
+        emitted.code.contains("/** What the pet does */\n    [\"role\"]?: string;"),
+        "{}",
+        emitted.code
+    );
+    // The member is on the third line, and must map back to the attribute
+    let mappings = emitted.source_map.split(r#""mappings":""#).nth(1).unwrap();
+    assert!(!mappings.split(';').nth(2).unwrap().is_empty(), "{}", emitted.source_map);
+}
+
+const MONGO_DATASTORES: &str = "module.exports.datastores = { default: { adapter: 'sails-mongo' } };";
+const SQL_DATASTORES: &str = "module.exports.datastores = { default: { adapter: 'sails-mysql' } };";
+const ID_MODEL: &str = "module.exports = { attributes: { id: { type: 'number', autoIncrement: true } } };";
+
+#[test]
+fn mongo_primary_keys_are_strings() {
+    let project = load_project("pk-mongo", &[("datastores.js", MONGO_DATASTORES)]);
+    let code = declare(ID_MODEL, &project);
+    assert!(code.contains(r#"["id"]: string;"#), "{}", code);
+}
+
+#[test]
+fn sql_primary_keys_follow_their_type() {
+    let project = load_project("pk-sql", &[("datastores.js", SQL_DATASTORES)]);
+    let code = declare(ID_MODEL, &project);
+    assert!(code.contains(r#"["id"]: number;"#), "{}", code);
+}
+
+#[test]
+fn dont_use_object_ids_keeps_mongo_primary_keys_numeric() {
+    let project = load_project(
+        "pk-dont-use-object-ids",
+        &[
+            ("datastores.js", MONGO_DATASTORES),
+            ("models.js", "module.exports.models = { dontUseObjectIds: true };"),
+        ],
+    );
+    let code = declare(ID_MODEL, &project);
+    assert!(code.contains(r#"["id"]: number;"#), "{}", code);
+}
+
+#[test]
+fn primary_key_setting_names_the_key() {
+    let code = declare(
+        "module.exports = {
+  primaryKey: 'code',
+  attributes: { code: { type: 'string', required: true } },
+};",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["code"]: string;"#), "{}", code);
+    assert!(code.contains(r#"Pet__PrimaryKey = "code";"#), "{}", code);
+}
+
+#[test]
+fn associations_use_the_primary_key_type_of_their_target() {
+    let project = load_project("pk-association", &[("datastores.js", MONGO_DATASTORES)]);
+    let owner = parse_model(ID_MODEL.to_string(), "User".to_string(), Some(PathBuf::from("/app/api/models/User.js")))
+        .unwrap();
+    let pet = parse_model(
+        "module.exports = { attributes: { owner: { model: 'user' } } };".to_string(),
+        "Pet".to_string(),
+        Some(PathBuf::from("/app/api/models/Pet.js")),
+    )
+    .unwrap();
+    let declaration_path = PathBuf::from("/app/types/Pet.d.ts");
+    let mut index = ModelIndex::default();
+    index.insert(&owner, &project, PathBuf::from("/app/types/User.d.ts"));
+    index.insert(&pet, &project, declaration_path.clone());
+    let code = emit_with_source_map(gen_decl(pet, &project, &index, &declaration_path).unwrap(), &declaration_path).code;
+    assert!(code.contains(r#"["owner"]?: string;"#), "{}", code);
+}
+
+/// The lines of the `__ModelDecl` interface.
+fn model_interface(code: &str) -> String {
+    code.lines().take_while(|line| *line != "}").collect::<Vec<_>>().join("\n")
+}
+
+#[test]
+fn schemaless_models_accept_any_key() {
+    let code = declare(
+        "module.exports = { schema: false, attributes: { name: { type: 'string' } } };",
+        &ProjectConfig::default(),
+    );
+    assert!(model_interface(&code).contains("[key: string]: unknown;"), "{}", code);
+}
+
+#[test]
+fn schema_setting_is_inherited_from_config() {
+    let project = load_project("schema-default", &[("models.js", "module.exports.models = { schema: false };")]);
+    let code = declare("module.exports = { attributes: { name: { type: 'string' } } };", &project);
+    assert!(model_interface(&code).contains("[key: string]: unknown;"), "{}", code);
+
+    let code = declare("module.exports = { schema: true, attributes: { name: { type: 'string' } } };", &project);
+    assert!(!code.contains("[key: string]"), "{}", code);
+}
+
+fn legacy() -> ProjectConfig {
+    ProjectConfig {
+        sails_version: SailsVersion::Legacy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn legacy_types_are_mapped() {
+    let code = declare(
+        "module.exports = {
+  attributes: {
+    bio: { type: 'text' },
+    age: { type: 'integer' },
+    weight: { type: 'float' },
+    born: { type: 'datetime' },
+    tags: { type: 'array' },
+    photo: { type: 'binary' },
+    email: { type: 'email' },
+    name: 'string',
+    kind: { type: 'string', enum: ['cat', 'dog'] },
+  },
+};",
+        &legacy(),
+    );
+    for expected in [
+        r#"["bio"]?: string;"#,
+        r#"["age"]?: number;"#,
+        r#"["weight"]?: number;"#,
+        r#"["born"]?: Date;"#,
+        r#"["tags"]?: any[];"#,
+        r#"["photo"]?: Buffer;"#,
+        r#"["email"]?: string;"#,
+        r#"["name"]?: string;"#,
+        r#"["kind"]?: "cat" | "dog";"#,
+    ] {
+        assert!(code.contains(expected), "{} not in {}", expected, code);
+    }
+}
+
+#[test]
+fn legacy_models_get_auto_attributes() {
+    let code = declare("module.exports = { attributes: { name: 'string' } };", &legacy());
+    assert!(code.contains(r#"["id"]: number;"#), "{}", code);
+    assert!(code.contains(r#"["createdAt"]: Date;"#), "{}", code);
+    assert!(code.contains(r#"["updatedAt"]: Date;"#), "{}", code);
+
+    let code = declare(
+        "module.exports = { autoPK: false, autoUpdatedAt: false, attributes: { name: 'string' } };",
+        &legacy(),
+    );
+    assert!(!code.contains(r#"["id"]"#), "{}", code);
+    assert!(code.contains(r#"["createdAt"]: Date;"#), "{}", code);
+    assert!(!code.contains(r#"["updatedAt"]"#), "{}", code);
+}
+
+#[test]
+fn hints_import_the_models_they_name() {
+    let project = ProjectConfig::default();
+    let user = parse_model(ID_MODEL.to_string(), "User".to_string(), Some(PathBuf::from("/app/api/models/User.js")))
+        .unwrap();
+    let pet = parse_model(
+        "module.exports = { attributes: { owners: { type: 'json', '$SD-type-hint': 'Array<User>' } } };".to_string(),
+        "Pet".to_string(),
+        Some(PathBuf::from("/app/api/models/Pet.js")),
+    )
+    .unwrap();
+    let declaration_path = PathBuf::from("/app/types/models/Pet.d.ts");
+    let mut index = ModelIndex::default();
+    index.insert(&user, &project, PathBuf::from("/app/types/models/User.d.ts"));
+    index.insert(&pet, &project, declaration_path.clone());
+    let code = emit_with_source_map(gen_decl(pet, &project, &index, &declaration_path).unwrap(), &declaration_path).code;
+    assert!(code.contains(r#"import type { User__ModelDecl as User } from "./User";"#), "{}", code);
+    assert!(code.contains(r#"["owners"]?: Array<User>;"#), "{}", code);
+}
+
+#[test]
+fn hints_import_configured_project_types() {
+    let project = ProjectConfig {
+        type_imports: vec![TypeImport::parse("Address=./lib/address", &PathBuf::from("/app")).unwrap()],
+        ..Default::default()
+    };
+    let code = declare(
+        "module.exports = { attributes: { home: { type: 'json', '$SD-type-hint': 'Address' } } };",
+        &project,
+    );
+    assert!(code.contains(r#"import type { Address } from "../lib/address";"#), "{}", code);
+    assert!(code.contains(r#"["home"]?: Address;"#), "{}", code);
+}
+
+#[test]
+fn import_types_are_rebased_onto_the_declaration() {
+    let code = declare(
+        "module.exports = { attributes: { home: { type: 'json', '$SD-type-hint': \"import('../types/geo').Point\" } } };",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["home"]?: import("../api/types/geo").Point;"#), "{}", code);
+}
+
+#[test]
+fn esm_default_exports_are_models() {
+    let code = declare_file(
+        "Pet.mjs",
+        "export default { attributes: { name: { type: 'string', required: true } } };",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]: string;"#), "{}", code);
+}
+
+#[test]
+fn typescript_models_are_parsed_as_typescript() {
+    let code = declare_file(
+        "Pet.ts",
+        "interface Attribute { type: string }
+const name: Attribute = { type: 'string' };
+export default { attributes: { name } } as const;",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]?: string;"#), "{}", code);
+}
+
+#[test]
+fn object_assign_exports_are_merged() {
+    let code = declare(
+        "const base = { attributes: { name: { type: 'string' } } };
+module.exports = Object.assign({}, base, { tableName: 'pets' });",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]?: string;"#), "{}", code);
+}
+
+#[test]
+fn exported_bindings_are_followed() {
+    let code = declare(
+        "const definition = { attributes: { name: { type: 'string' } } };
+module.exports = definition;",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]?: string;"#), "{}", code);
+}
+
+#[test]
+fn property_assignments_on_exports_are_merged() {
+    let code = declare(
+        "module.exports = { primaryKey: 'code' };
+module.exports.attributes = { code: { type: 'string' } };",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["code"]: string;"#), "{}", code);
+
+    let code = declare(
+        "exports.schema = false;
+exports.attributes = { name: { type: 'string' } };",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]?: string;"#), "{}", code);
+    assert!(code.contains("[key: string]: unknown;"), "{}", code);
+}
+
+#[test]
+fn exports_is_ignored_once_module_exports_is_reassigned() {
+    let code = declare(
+        "exports.schema = false;
+module.exports = { attributes: { name: { type: 'string' } } };
+exports.attributes = { nickname: { type: 'string' } };",
+        &ProjectConfig::default(),
+    );
+    assert!(code.contains(r#"["name"]?: string;"#), "{}", code);
+    assert!(!code.contains(r#"["nickname"]"#), "{}", code);
+    assert!(!code.contains("[key: string]"), "{}", code);
+}
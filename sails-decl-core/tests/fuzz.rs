@@ -0,0 +1,365 @@
+//! Feeds randomly generated, syntactically valid models and helpers through the
+//! whole pipeline to check that no input makes it panic.
+//!
+//! Runs are deterministic. Set `SAILS_DECL_FUZZ_ITERATIONS` to run more cases
+//! and `SAILS_DECL_FUZZ_SEED` to start from a different seed.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::{Path, PathBuf};
+
+use sails_decl_core::config::{ProjectConfig, SailsVersion};
+use sails_decl_core::helpers::get_helper_info;
+use sails_decl_core::model::{ModelIndex, emit_with_source_map, gen_decl, parse_model};
+use swc_common::SourceMap;
+use swc_common::sync::Lrc;
+
+/// xorshift64*, so the harness needs no extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+const BINDINGS: &[&str] = &["TYPES", "STATUSES", "base", "shared", "frozen", "missing", "undefined"];
+
+const TYPE_NAMES: &[&str] = &[
+    "string", "number", "boolean", "json", "ref", "text", "integer", "float", "date", "datetime", "binary",
+    "array", "objectid", "", "String", "💥",
+];
+
+const TYPE_HINTS: &[&str] = &[
+    "string",
+    "Array<{ a: number }>",
+    "'a' | 'b'",
+    "import('./types').Thing",
+    "User",
+    "Record<string, unknown>",
+    "{",
+    "string; declare const x: 1",
+    "",
+    "}}{{",
+    "a\\nb",
+    "typeof import('x')",
+];
+
+const KEYS: &[&str] = &[
+    "type", "required", "allowNull", "defaultsTo", "description", "extendedDescription", "example", "moreInfoUrl",
+    "isIn", "isEmail", "min", "maxLength", "regex", "enum", "model", "collection", "via", "columnName",
+    "encrypt", "protect", "autoIncrement", "unique", "$SD-type-hint", "custom",
+];
+
+fn gen_string(rng: &mut Rng) -> String {
+    let value = rng.pick(&[
+        "", "string", "user", "a'b", "multi\\nline\\n\\ntext", "*/ closes", "ünïcødé", "\\u0000", "${x}", "@type {x}",
+    ]);
+    match rng.below(3) {
+        0 => format!("'{}'", value),
+        1 => format!("\"{}\"", value),
+        _ => format!("`{}`", value),
+    }
+}
+
+fn gen_expr(rng: &mut Rng, depth: usize) -> String {
+    let leaf = depth == 0 || rng.chance(40);
+    match if leaf { rng.below(8) } else { rng.below(18) } {
+        0 => gen_string(rng),
+        1 => rng.pick(&["0", "-1", "1.5", "1e21", "0x10", "NaN", "-Infinity"]).to_string(),
+        2 => rng.pick(&["true", "false", "null", "undefined"]).to_string(),
+        3 => rng.pick(&["/a+/gi", "/[*/]/", "/(?<x>.)/u"]).to_string(),
+        4 => rng.pick(BINDINGS).to_string(),
+        5 => format!("'{}'", rng.pick(TYPE_NAMES)),
+        6 => rng.pick(&["TYPES.STRING", "TYPES['NUMBER']", "STATUSES[0]", "STATUSES[99]", "base.attributes", "missing.x"]).to_string(),
+        7 => "`tpl ${1 + 1}`".to_string(),
+        8 => format!("[{}]", gen_list(rng, depth - 1)),
+        9 => gen_object(rng, depth - 1),
+        10 => format!("Object.freeze({})", gen_expr(rng, depth - 1)),
+        11 => format!("Object.assign({{}}, {}, {})", gen_expr(rng, depth - 1), gen_expr(rng, depth - 1)),
+        12 => format!("require('{}')", rng.pick(&["./shared", "./cycle", "./missing", "lodash", "../models/Model0", "./"])),
+        13 => format!("({}) => {}", rng.pick(&["", "a", "{ a }"]), gen_expr(rng, depth - 1)),
+        14 => "function (x) { return _.omit(this, ['password', x]); }".to_string(),
+        15 => format!("({})", gen_expr(rng, depth - 1)),
+        16 => format!("-{}", gen_expr(rng, depth - 1)),
+        _ => format!("{} ? {} : {}", gen_expr(rng, 0), gen_expr(rng, depth - 1), gen_expr(rng, depth - 1)),
+    }
+}
+
+fn gen_list(rng: &mut Rng, depth: usize) -> String {
+    (0..rng.below(4))
+        .map(|_| match rng.below(6) {
+            0 => format!("...{}", gen_expr(rng, depth)),
+            1 => String::new(),
+            _ => gen_expr(rng, depth),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn gen_prop(rng: &mut Rng, key: &str, depth: usize) -> String {
+    let key = if key.starts_with('$') || rng.chance(15) {
+        format!("'{}'", key)
+    } else {
+        key.to_string()
+    };
+    match rng.below(12) {
+        0 => format!("...{}", gen_expr(rng, depth)),
+        1 => rng.pick(BINDINGS).to_string(),
+        2 => format!("{}(value) {{ return !!value; }}", key),
+        3 => format!("get {}() {{ return 1; }}", key),
+        4 => format!("set {}(value) {{}}", key),
+        5 => format!("[{}]: {}", gen_expr(rng, 0), gen_expr(rng, depth)),
+        6 => format!("{}: {}", rng.below(3), gen_expr(rng, depth)),
+        _ => format!("{}: {}", key, gen_expr(rng, depth)),
+    }
+}
+
+fn gen_object(rng: &mut Rng, depth: usize) -> String {
+    let props = (0..rng.below(4))
+        .map(|_| {
+            let key = rng.pick(KEYS);
+            gen_prop(rng, key, depth)
+        })
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", props.join(", "))
+}
+
+fn gen_attribute(rng: &mut Rng) -> String {
+    let mut props = vec![];
+    for _ in 0..rng.below(6) {
+        let key = rng.pick(KEYS);
+        let prop = match key {
+            "type" if rng.chance(70) => format!("type: '{}'", rng.pick(TYPE_NAMES)),
+            "$SD-type-hint" => format!("'$SD-type-hint': '{}'", rng.pick(TYPE_HINTS)),
+            "model" if rng.chance(70) => format!("model: '{}'", rng.pick(&["model0", "model1", "nope", ""])),
+            "required" | "allowNull" | "encrypt" | "protect" if rng.chance(70) => {
+                format!("{}: {}", key, rng.pick(&["true", "false"]))
+            }
+            "enum" | "isIn" if rng.chance(70) => format!("{}: [{}]", key, gen_list(rng, 1)),
+            _ => gen_prop(rng, key, 2),
+        };
+        props.push(prop);
+    }
+    format!("{{ {} }}", props.join(", "))
+}
+
+fn gen_jsdoc(rng: &mut Rng) -> String {
+    match rng.below(5) {
+        0 => format!("/** @type {{{}}} */ ", rng.pick(TYPE_HINTS)),
+        1 => "/** @type {{ a: { b: string } }} */ ".to_string(),
+        2 => "/** @type */ ".to_string(),
+        3 => "/* @type {string} */ ".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn gen_attributes(rng: &mut Rng) -> String {
+    let mut props = vec![];
+    for _ in 0..rng.below(6) {
+        let name = rng.pick(&["id", "name", "email", "owner", "createdAt", "password", "'first-name'", "'quoted'", "0"]);
+        let prop = match rng.below(10) {
+            0 => format!("...{}", gen_expr(rng, 2)),
+            1 => format!("{}: '{}'", name, rng.pick(TYPE_NAMES)),
+            2 => format!("{}: {}", name, gen_expr(rng, 2)),
+            3 => rng.pick(BINDINGS).to_string(),
+            4 => format!("{}() {{}}", name),
+            _ => format!("{}{}: {}", gen_jsdoc(rng), name, gen_attribute(rng)),
+        };
+        props.push(prop);
+    }
+    format!("{{ {} }}", props.join(",\n    "))
+}
+
+fn gen_preamble(rng: &mut Rng) -> String {
+    let mut preamble = vec![];
+    if rng.chance(50) {
+        preamble.push("const TYPES = Object.freeze({ STRING: 'string', NUMBER: 'number' });".to_string());
+    }
+    if rng.chance(50) {
+        preamble.push("const STATUSES = ['draft', 'published'];".to_string());
+    }
+    if rng.chance(50) {
+        preamble.push(format!("const base = {{ attributes: {} }};", gen_attributes(rng)));
+    }
+    if rng.chance(30) {
+        preamble.push(format!("var shared = {};", gen_expr(rng, 2)));
+    }
+    if rng.chance(30) {
+        preamble.push("let { frozen } = require('./shared');".to_string());
+    }
+    preamble.join("\n")
+}
+
+fn gen_definition(rng: &mut Rng) -> String {
+    let mut props = vec![];
+    if rng.chance(90) {
+        let attributes = match rng.below(8) {
+            0 => gen_expr(rng, 2),
+            1 => "base.attributes".to_string(),
+            _ => gen_attributes(rng),
+        };
+        props.push(format!("attributes: {}", attributes));
+    }
+    for _ in 0..rng.below(4) {
+        let prop = match rng.below(8) {
+            0 => format!("primaryKey: {}", gen_expr(rng, 1)),
+            1 => format!("schema: {}", rng.pick(&["false", "true", "'no'"])),
+            2 => format!("dontUseObjectIds: {}", rng.pick(&["true", "false"])),
+            3 => format!("datastore: {}", gen_string(rng)),
+            4 => "customToJSON: function () { return _.omit(this, ['password', 'id']); }".to_string(),
+            5 => "customToJSON() { return _.omit(this, 'password'); }".to_string(),
+            6 => "customToJSON: () => _.omit(this, [1, 'x'])".to_string(),
+            _ => format!("autoPK: {}", gen_expr(rng, 0)),
+        };
+        props.push(prop);
+    }
+    if rng.chance(10) {
+        props.push("...base".to_string());
+    }
+    format!("{{\n  {}\n}}", props.join(",\n  "))
+}
+
+fn gen_module(rng: &mut Rng, extension: &str, definition: String) -> String {
+    let preamble = gen_preamble(rng);
+    let exports = if extension == "mjs" || (extension == "ts" && rng.chance(50)) {
+        match rng.below(3) {
+            0 => format!("export default {};", definition),
+            1 => format!("export const attributes = {};", gen_attributes(rng)),
+            _ => format!("const def = {};\nexport default def;", definition),
+        }
+    } else {
+        match rng.below(8) {
+            0 => format!("const def = {};\nmodule.exports = def;", definition),
+            1 => format!("module.exports = Object.assign({{}}, base, {});", definition),
+            2 => format!("exports.attributes = {};", gen_attributes(rng)),
+            3 => format!("module.exports.attributes = {};\nObject.assign(module.exports, {});", gen_attributes(rng), definition),
+            4 => format!("module.exports = {};", gen_expr(rng, 2)),
+            5 => "if (true) { module.exports = {}; }".to_string(),
+            _ => format!("module.exports = {};", definition),
+        }
+    };
+    format!("{}\n{}\n", preamble, exports)
+}
+
+fn gen_helper(rng: &mut Rng, extension: &str) -> String {
+    let mut props = vec![format!("friendlyName: {}", gen_string(rng))];
+    if rng.chance(60) {
+        props.push(format!("description: {}", gen_string(rng)));
+    }
+    if rng.chance(85) {
+        let inputs = match rng.below(6) {
+            0 => gen_expr(rng, 2),
+            _ => gen_attributes(rng),
+        };
+        props.push(format!("inputs: {}", inputs));
+    }
+    props.push(
+        rng.pick(&[
+            "fn: async function (inputs) { return inputs; }",
+            "fn: async ({ name }) => name",
+            "async fn(inputs, exits) { return exits.success(1); }",
+            "fn: function () {}",
+            "fn: 42",
+        ])
+        .to_string(),
+    );
+    gen_module(rng, extension, format!("{{\n  {}\n}}", props.join(",\n  ")))
+}
+
+fn check_case(dir: &Path, seed: u64) {
+    let mut rng = Rng(seed);
+    let extension = rng.pick(&["js", "cjs", "mjs", "ts"]);
+
+    let shared = gen_definition(&mut rng);
+    std::fs::write(dir.join("shared.js"), gen_module(&mut rng, "js", shared)).unwrap();
+    std::fs::write(dir.join("cycle.js"), "module.exports = { ...require('./cycle'), a: { type: 'string' } };").unwrap();
+
+    let mut project = ProjectConfig::default();
+    if rng.chance(30) {
+        project.sails_version = SailsVersion::Legacy;
+    }
+    project.strict_type_hints = rng.chance(30);
+
+    let mut models = vec![];
+    for i in 0..2 {
+        let path = dir.join(format!("Model{}.{}", i, extension));
+        let definition = gen_definition(&mut rng);
+        let code = gen_module(&mut rng, extension, definition);
+        std::fs::write(&path, &code).unwrap();
+        models.push((path, code));
+    }
+
+    let helper_path: PathBuf = dir.join(format!("do-thing.{}", extension));
+    let helper_code = gen_helper(&mut rng, extension);
+    std::fs::write(&helper_path, &helper_code).unwrap();
+
+    let sources = models
+        .iter()
+        .map(|(path, code)| format!("// {}\n{}", path.display(), code))
+        .chain([format!("// {}\n{}", helper_path.display(), helper_code)])
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let parsed = models
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (path, code))| parse_model(code.clone(), format!("Model{}", i), Some(path.clone())).ok())
+            .collect::<Vec<_>>();
+
+        let mut index = ModelIndex::default();
+        for model in &parsed {
+            index.insert(model, &project, dir.join("types").join(format!("{}.d.ts", model.name)));
+        }
+        for model in parsed {
+            let declaration_path = dir.join("types").join(format!("{}.d.ts", model.name));
+            if let Ok(decl) = gen_decl(model, &project, &index, &declaration_path) {
+                emit_with_source_map(decl, &declaration_path);
+            }
+        }
+
+        let cm: Lrc<SourceMap> = Default::default();
+        let _ = get_helper_info(helper_path.clone(), cm, &project);
+    }));
+
+    if result.is_err() {
+        panic!("seed {} panicked on:\n{}", seed, sources);
+    }
+}
+
+#[test]
+fn random_modules_do_not_panic() {
+    let iterations = std::env::var("SAILS_DECL_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(200u64);
+    let first_seed = std::env::var("SAILS_DECL_FUZZ_SEED")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1u64);
+
+    let dir = std::env::temp_dir().join(format!("sails-decl-fuzz-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for seed in first_seed..first_seed + iterations {
+        // xorshift never leaves zero
+        check_case(&dir, seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+    }
+
+    std::fs::remove_dir_all(&dir).ok();
+}